@@ -1,5 +1,10 @@
 use tauri::{State, Window, Manager};
-use crate::engine::{Engine, QualityMode, AspectMode, SyncMode};
+use crate::engine::{
+    AlphaMode, AnimationLoop, ArchiveMediaEntry, AspectMode, AudioOutputFormat, BackgroundMode,
+    Chapter, ChapterStep, DeinterlaceMode, DownmixMode, Engine, MediaProbe, QualityMode,
+    ResampleQuality, SeekMode, StreamInfo, StreamKind, SubtitleFileError, SubtitleSelection,
+    SyncMode, ToneMapOperator,
+};
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::atomic::Ordering;
@@ -36,6 +41,19 @@ pub async fn open_video(
         *s += 1;
     }
 
+    // Track choices belong to the previous file
+    engine.state.media_streams.lock().unwrap().clear();
+    *engine.state.video_stream_request.lock().unwrap() = None;
+    *engine.state.audio_stream_request.lock().unwrap() = None;
+    *engine.state.subtitle_request.lock().unwrap() = None;
+    *engine.state.subtitle_file.lock().unwrap() = None;
+    *engine.state.subtitle_offset.lock().unwrap() = 0.0;
+    engine.state.chapters.lock().unwrap().clear();
+    *engine.state.chapter_request.lock().unwrap() = None;
+    *engine.state.image_sequence.lock().unwrap() = None;
+    *engine.state.follow_mode.lock().unwrap() = false;
+    *engine.state.follow_idle_timeout.lock().unwrap() = crate::engine::DEFAULT_FOLLOW_IDLE_TIMEOUT;
+
     {
         let mut p = engine.state.is_playing.lock().unwrap();
         *p = true; 
@@ -67,6 +85,13 @@ pub fn set_sync_mode(engine: State<'_, Engine>, mode: SyncMode) {
     *guard = mode;
 }
 
+#[tauri::command]
+pub fn set_seek_mode(engine: State<'_, Engine>, mode: SeekMode) {
+    eprintln!("[Command] Setting SeekMode to {:?}", mode);
+    let mut guard = engine.state.seek_mode.lock().unwrap();
+    *guard = mode;
+}
+
 #[tauri::command]
 pub fn seek_video(engine: State<'_, Engine>, time: f64) {
     eprintln!("[Command] seek_video requested to {}s", time);
@@ -99,6 +124,54 @@ pub fn set_aspect_ratio(engine: State<'_, Engine>, mode: AspectMode) {
     }
 }
 
+#[tauri::command]
+pub fn set_tone_mapping(
+    engine: State<'_, Engine>,
+    operator: ToneMapOperator,
+    target_peak: f32,
+) -> Result<(), String> {
+    eprintln!("[Command] Setting Tone Mapping: {:?} at {} nits", operator, target_peak);
+    if !target_peak.is_finite() || !(50.0..=1000.0).contains(&target_peak) {
+        return Err(format!("Target peak must be between 50 and 1000 nits, got {}", target_peak));
+    }
+    let mut renderer_guard = engine.state.renderer.lock().unwrap();
+    if let Some(r) = renderer_guard.as_mut() {
+        r.set_tone_mapping(operator, target_peak);
+        let _ = r.repaint();
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_background(engine: State<'_, Engine>, mode: BackgroundMode) {
+    eprintln!("[Command] Setting Background: {:?}", mode);
+    let mut renderer_guard = engine.state.renderer.lock().unwrap();
+    if let Some(r) = renderer_guard.as_mut() {
+        r.set_background(mode);
+        let _ = r.repaint();
+    }
+}
+
+#[tauri::command]
+pub fn set_alpha_mode(engine: State<'_, Engine>, mode: AlphaMode) {
+    eprintln!("[Command] Setting Alpha Mode: {:?}", mode);
+    let mut renderer_guard = engine.state.renderer.lock().unwrap();
+    if let Some(r) = renderer_guard.as_mut() {
+        r.set_alpha_mode(mode);
+        let _ = r.repaint();
+    }
+}
+
+#[tauri::command]
+pub fn set_alpha_only(engine: State<'_, Engine>, enabled: bool) {
+    eprintln!("[Command] Setting Alpha Only View: {}", enabled);
+    let mut renderer_guard = engine.state.renderer.lock().unwrap();
+    if let Some(r) = renderer_guard.as_mut() {
+        r.set_alpha_only(enabled);
+        let _ = r.repaint();
+    }
+}
+
 #[tauri::command]
 pub async fn init_renderer(window: Window, engine: State<'_, Engine>) -> Result<(), String> {
     let needs_init = engine.state.renderer.lock().unwrap().is_none();
@@ -120,6 +193,243 @@ pub fn set_volume(engine: State<'_, Engine>, volume: f32) {
     let vol_int = (volume.clamp(0.0, 1.0) * 1000.0) as u32;
     engine.state.volume.store(vol_int, Ordering::Relaxed);
 }
+
+#[tauri::command]
+pub fn set_resample_quality(engine: State<'_, Engine>, quality: ResampleQuality) {
+    eprintln!("[Command] Setting ResampleQuality to {:?}", quality);
+    let mut guard = engine.state.resample_quality.lock().unwrap();
+    *guard = quality;
+}
+
+#[tauri::command]
+pub fn set_deinterlace_mode(engine: State<'_, Engine>, mode: DeinterlaceMode) {
+    eprintln!("[Command] Setting DeinterlaceMode to {:?}", mode);
+    let mut guard = engine.state.deinterlace_mode.lock().unwrap();
+    *guard = mode;
+}
+
+#[tauri::command]
+pub fn set_video_filter(engine: State<'_, Engine>, filter: Option<String>) -> Result<(), String> {
+    let filter = filter
+        .map(|f| f.trim().to_string())
+        .filter(|f| !f.is_empty());
+    eprintln!("[Command] Setting video filter: {:?}", filter);
+    if let Some(ref spec) = filter {
+        // Check against the active video stream's size when there is one
+        let (width, height) = engine
+            .state
+            .media_streams
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|s| s.kind == StreamKind::Video && s.active)
+            .and_then(|s| Some((s.width?, s.height?)))
+            .unwrap_or((1280, 720));
+        crate::engine::media::validate_video_filter(spec, width, height)
+            .map_err(|e| e.to_string())?;
+    }
+    *engine.state.video_filter.lock().unwrap() = filter;
+    Ok(())
+}
+
+// Frame rate for image sequences; applies from the next `open_video`
+#[tauri::command]
+pub fn set_sequence_frame_rate(engine: State<'_, Engine>, fps: f64) -> Result<(), String> {
+    eprintln!("[Command] Setting sequence frame rate: {}", fps);
+    if !fps.is_finite() || fps <= 0.0 || fps > 1000.0 {
+        return Err(format!("Frame rate must be between 0 and 1000 fps, got {}", fps));
+    }
+    *engine.state.sequence_frame_rate.lock().unwrap() = fps;
+    Ok(())
+}
+
+// Whether animated GIF/APNG/WebP restart at the end; applies at the next loop point
+#[tauri::command]
+pub fn set_animation_loop(engine: State<'_, Engine>, mode: AnimationLoop) {
+    eprintln!("[Command] Setting animation loop: {:?}", mode);
+    *engine.state.animation_loop.lock().unwrap() = mode;
+}
+
+// Follow files still being written; switches itself off after `idle_timeout` seconds
+// without growth
+#[tauri::command]
+pub fn set_follow_mode(
+    engine: State<'_, Engine>,
+    enabled: bool,
+    idle_timeout: f64,
+) -> Result<(), String> {
+    eprintln!(
+        "[Command] Setting follow mode: {} (idle timeout {}s)",
+        enabled, idle_timeout
+    );
+    if !idle_timeout.is_finite() || idle_timeout <= 0.0 || idle_timeout > 3600.0 {
+        return Err(format!(
+            "Idle timeout must be between 0 and 3600 seconds, got {}",
+            idle_timeout
+        ));
+    }
+    *engine.state.follow_idle_timeout.lock().unwrap() = idle_timeout;
+    *engine.state.follow_mode.lock().unwrap() = enabled;
+    Ok(())
+}
+
+// The image file showing at `time` when an image sequence is open, for frame export
+#[tauri::command]
+pub fn sequence_frame_at(engine: State<'_, Engine>, time: f64) -> Option<String> {
+    engine
+        .state
+        .image_sequence
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|sequence| sequence.frame_at(time).to_string_lossy().into_owned())
+}
+
+#[tauri::command]
+pub fn get_audio_output_format(engine: State<'_, Engine>) -> AudioOutputFormat {
+    engine.state.audio_format.lock().unwrap().clone()
+}
+
+#[tauri::command]
+pub fn set_downmix_mode(engine: State<'_, Engine>, mode: DownmixMode) {
+    eprintln!("[Command] Setting DownmixMode to {:?}", mode);
+    let mut guard = engine.state.downmix_mode.lock().unwrap();
+    *guard = mode;
+}
+
+#[tauri::command]
+pub fn list_streams(engine: State<'_, Engine>) -> Vec<StreamInfo> {
+    engine.state.media_streams.lock().unwrap().clone()
+}
+
+fn check_stream_kind(engine: &Engine, index: usize, kind: StreamKind) -> Result<(), String> {
+    let streams = engine.state.media_streams.lock().unwrap();
+    match streams.iter().find(|s| s.index == index) {
+        Some(s) if s.kind == kind => Ok(()),
+        Some(s) => Err(format!("Stream {} is {:?}, not {:?}", index, s.kind, kind)),
+        None => Err(format!("No stream at index {}", index)),
+    }
+}
+
+#[tauri::command]
+pub fn select_video_stream(engine: State<'_, Engine>, index: usize) -> Result<(), String> {
+    eprintln!("[Command] Selecting video stream {}", index);
+    check_stream_kind(&engine, index, StreamKind::Video)?;
+    *engine.state.video_stream_request.lock().unwrap() = Some(index);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn select_audio_stream(engine: State<'_, Engine>, index: usize) -> Result<(), String> {
+    eprintln!("[Command] Selecting audio stream {}", index);
+    check_stream_kind(&engine, index, StreamKind::Audio)?;
+    *engine.state.audio_stream_request.lock().unwrap() = Some(index);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_subtitle_tracks(engine: State<'_, Engine>) -> Vec<StreamInfo> {
+    engine
+        .state
+        .media_streams
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|s| s.kind == StreamKind::Subtitle)
+        .cloned()
+        .collect()
+}
+
+#[tauri::command]
+pub fn select_subtitle_track(engine: State<'_, Engine>, index: usize) -> Result<(), String> {
+    eprintln!("[Command] Selecting subtitle stream {}", index);
+    check_stream_kind(&engine, index, StreamKind::Subtitle)?;
+    *engine.state.subtitle_request.lock().unwrap() = Some(SubtitleSelection::Track(index));
+    Ok(())
+}
+
+#[tauri::command]
+pub fn disable_subtitles(engine: State<'_, Engine>) {
+    eprintln!("[Command] Disabling subtitles");
+    *engine.state.subtitle_request.lock().unwrap() = Some(SubtitleSelection::Off);
+}
+
+// Replaces any embedded track with the file's cues; returns how many were loaded
+#[tauri::command]
+pub fn load_subtitle_file(
+    engine: State<'_, Engine>,
+    path: String,
+) -> Result<usize, SubtitleFileError> {
+    eprintln!("[Command] Loading subtitle file {}", path);
+    let file = crate::engine::media::SubtitleFile::load(&PathBuf::from(&path)).map_err(|e| {
+        eprintln!("[Command] Subtitle file rejected: {}", e);
+        e
+    })?;
+    let count = file.cue_count();
+    *engine.state.subtitle_file.lock().unwrap() = Some(Arc::new(file));
+    *engine.state.subtitle_request.lock().unwrap() = Some(SubtitleSelection::File);
+    Ok(count)
+}
+
+#[tauri::command]
+pub fn set_subtitle_offset(engine: State<'_, Engine>, offset: f64) -> Result<(), String> {
+    eprintln!("[Command] Setting subtitle offset to {}s", offset);
+    if !offset.is_finite() {
+        return Err(format!("Subtitle offset must be a number of seconds, got {}", offset));
+    }
+    let mut guard = engine.state.subtitle_offset.lock().unwrap();
+    *guard = offset;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_chapters(engine: State<'_, Engine>) -> Vec<Chapter> {
+    engine.state.chapters.lock().unwrap().clone()
+}
+
+fn request_chapter_step(engine: &Engine, step: ChapterStep) -> Result<(), String> {
+    eprintln!("[Command] Chapter step {:?}", step);
+    if engine.state.chapters.lock().unwrap().is_empty() {
+        return Err("Media has no chapters".to_string());
+    }
+    *engine.state.chapter_request.lock().unwrap() = Some(step);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn next_chapter(engine: State<'_, Engine>) -> Result<(), String> {
+    request_chapter_step(&engine, ChapterStep::Next)
+}
+
+#[tauri::command]
+pub fn previous_chapter(engine: State<'_, Engine>) -> Result<(), String> {
+    request_chapter_step(&engine, ChapterStep::Previous)
+}
+
+// Media files inside a ZIP archive, with paths `open_video` accepts
+#[tauri::command]
+pub async fn list_archive_media(path: String) -> Result<Vec<ArchiveMediaEntry>, String> {
+    // Reading the central directory is blocking file I/O; keep it off the async runtime
+    tauri::async_runtime::spawn_blocking(move || {
+        crate::engine::media::list_archive_media(&PathBuf::from(path))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn probe_media(engine: State<'_, Engine>, path: String) -> Result<MediaProbe, String> {
+    let sequence_frame_rate = *engine.state.sequence_frame_rate.lock().unwrap();
+    // Opening the file can take a while on network shares; keep it off the async runtime
+    tauri::async_runtime::spawn_blocking(move || {
+        crate::engine::media::probe_media(&PathBuf::from(path), sequence_frame_rate)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub fn get_app_cache_dir(app: tauri::AppHandle) -> Result<String, String> {
     let path = app
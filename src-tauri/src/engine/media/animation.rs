@@ -0,0 +1,387 @@
+use super::super::types::{AnimationFormat, AnimationInfo};
+use std::path::Path;
+
+/// Display time of a frame stored with `delay_ms`. Like FFmpeg's GIF demuxer (and
+/// browsers), delays under 20 ms are taken as 100 ms: such files rely on it.
+pub fn frame_delay_ms(delay_ms: u32) -> u32 {
+    if delay_ms < 20 {
+        100
+    } else {
+        delay_ms
+    }
+}
+
+fn u16_le(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u16::from_le_bytes(data.get(pos..pos + 2)?.try_into().ok()?) as u32)
+}
+
+fn u24_le(data: &[u8], pos: usize) -> Option<u32> {
+    let bytes = data.get(pos..pos + 3)?;
+    Some(bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16)
+}
+
+fn u32_le(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+fn u16_be(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u16::from_be_bytes(data.get(pos..pos + 2)?.try_into().ok()?) as u32)
+}
+
+fn u32_be(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+/// Position after a run of GIF data sub-blocks starting at `pos`.
+fn skip_gif_sub_blocks(data: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *data.get(pos)? as usize;
+        pos += 1 + len;
+        if len == 0 {
+            return Some(pos);
+        }
+    }
+}
+
+/// Counts image descriptors and sums graphic control delays. Files without a NETSCAPE2.0
+/// loop extension play once.
+fn parse_gif(data: &[u8]) -> Option<AnimationInfo> {
+    if !data.starts_with(b"GIF87a") && !data.starts_with(b"GIF89a") {
+        return None;
+    }
+    let color_table = |flags: u8| {
+        if flags & 0x80 != 0 {
+            3usize << ((flags & 0x07) + 1)
+        } else {
+            0
+        }
+    };
+    let mut pos = 13 + color_table(*data.get(10)?);
+    let mut frame_count = 0;
+    let mut loop_count = 1;
+    let mut duration_ms = 0u64;
+    let mut delay_cs = 0;
+
+    // A truncated file reports the frames read so far
+    let mut read_block = |pos: usize| -> Option<usize> {
+        match *data.get(pos)? {
+            0x21 => {
+                let label = *data.get(pos + 1)?;
+                let body = pos + 2;
+                if label == 0xF9 {
+                    delay_cs = u16_le(data, body + 2)?;
+                } else if label == 0xFF
+                    && data.get(body..body + 12) == Some(b"\x0bNETSCAPE2.0".as_slice())
+                    && data.get(body + 12..body + 14) == Some([3, 1].as_slice())
+                {
+                    // The extension counts repeats after the first play
+                    loop_count = match u16_le(data, body + 14)? {
+                        0 => 0,
+                        repeats => repeats + 1,
+                    };
+                }
+                skip_gif_sub_blocks(data, body)
+            }
+            0x2C => {
+                frame_count += 1;
+                duration_ms += frame_delay_ms(delay_cs * 10) as u64;
+                delay_cs = 0;
+                // Descriptor, local colour table, LZW code size, then the image data
+                let body = pos + 10 + color_table(*data.get(pos + 9)?) + 1;
+                skip_gif_sub_blocks(data, body)
+            }
+            _ => None,
+        }
+    };
+    while let Some(next) = read_block(pos) {
+        pos = next;
+    }
+
+    (frame_count > 1).then(|| AnimationInfo {
+        format: AnimationFormat::Gif,
+        frame_count,
+        loop_count,
+        duration: duration_ms as f64 / 1000.0,
+    })
+}
+
+/// Reads the acTL chunk and sums the fcTL delays; a PNG without acTL is a still.
+fn parse_apng(data: &[u8]) -> Option<AnimationInfo> {
+    if !data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return None;
+    }
+    let mut pos = 8;
+    let mut control = None;
+    let mut duration_ms = 0.0;
+    while let (Some(len), Some(kind)) = (u32_be(data, pos), data.get(pos + 4..pos + 8)) {
+        let body = pos + 8;
+        match kind {
+            b"acTL" => control = Some((u32_be(data, body)?, u32_be(data, body + 4)?)),
+            b"fcTL" => {
+                let (num, den) = match (u16_be(data, body + 20), u16_be(data, body + 22)) {
+                    (Some(num), Some(den)) => (num, den),
+                    _ => break,
+                };
+                // A zero denominator means hundredths of a second
+                let den = if den == 0 { 100 } else { den };
+                duration_ms += num as f64 * 1000.0 / den as f64;
+            }
+            b"IEND" => break,
+            _ => {}
+        }
+        // Chunk data, then its CRC
+        pos = body + len as usize + 4;
+    }
+
+    let (frame_count, loop_count) = control?;
+    (frame_count > 1).then(|| AnimationInfo {
+        format: AnimationFormat::Apng,
+        frame_count,
+        loop_count,
+        duration: duration_ms / 1000.0,
+    })
+}
+
+/// Reads the ANIM chunk and counts ANMF frames; a WebP without them is a still.
+fn parse_webp(data: &[u8]) -> Option<AnimationInfo> {
+    if data.get(0..4) != Some(b"RIFF".as_slice()) || data.get(8..12) != Some(b"WEBP".as_slice()) {
+        return None;
+    }
+    let mut pos = 12;
+    let mut loop_count = None;
+    let mut frame_count = 0;
+    let mut duration_ms = 0u64;
+    while let (Some(kind), Some(len)) = (data.get(pos..pos + 4), u32_le(data, pos + 4)) {
+        let body = pos + 8;
+        match kind {
+            b"ANIM" => loop_count = u16_le(data, body + 4),
+            b"ANMF" => {
+                frame_count += 1;
+                duration_ms += frame_delay_ms(u24_le(data, body + 12).unwrap_or(0)) as u64;
+            }
+            _ => {}
+        }
+        // Chunks are padded to an even size
+        pos = body + len as usize + (len as usize & 1);
+    }
+
+    (frame_count > 1).then(|| AnimationInfo {
+        format: AnimationFormat::WebP,
+        frame_count,
+        loop_count: loop_count.unwrap_or(0),
+        duration: duration_ms as f64 / 1000.0,
+    })
+}
+
+/// Frame and loop counts of an animated GIF, APNG or WebP; `None` for anything else,
+/// stills included.
+pub fn probe_animation(path: &Path) -> Option<AnimationInfo> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    if !matches!(extension.as_str(), "gif" | "png" | "apng" | "webp") {
+        return None;
+    }
+    let data = std::fs::read(path).ok()?;
+    let info = parse_gif(&data)
+        .or_else(|| parse_apng(&data))
+        .or_else(|| parse_webp(&data))?;
+    log::info!(
+        "[Animation] {:?}: {} frames, {:.2}s, loop count {}",
+        info.format,
+        info.frame_count,
+        info.duration,
+        info.loop_count
+    );
+    Some(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A GIF with one 1x1 image per delay (in hundredths of a second), plus a NETSCAPE2.0
+    /// extension when `repeats` is given.
+    fn gif(delays_cs: &[u16], repeats: Option<u16>) -> Vec<u8> {
+        let mut data = b"GIF89a\x01\x00\x01\x00\x00\x00\x00".to_vec();
+        if let Some(repeats) = repeats {
+            data.extend(b"\x21\xff\x0bNETSCAPE2.0\x03\x01");
+            data.extend(repeats.to_le_bytes());
+            data.push(0);
+        }
+        for delay in delays_cs {
+            data.extend(b"\x21\xf9\x04\x00");
+            data.extend(delay.to_le_bytes());
+            data.extend(b"\x00\x00");
+            data.extend(b"\x2c\x00\x00\x00\x00\x01\x00\x01\x00\x00");
+            data.extend(b"\x02\x02\x4c\x01\x00");
+        }
+        data.push(0x3b);
+        data
+    }
+
+    fn png_chunk(data: &mut Vec<u8>, kind: &[u8], body: &[u8]) {
+        data.extend((body.len() as u32).to_be_bytes());
+        data.extend(kind);
+        data.extend(body);
+        data.extend([0; 4]); // CRC, unchecked
+    }
+
+    /// A PNG with an acTL chunk when `plays` is given and one fcTL per (num, den) delay.
+    fn apng(delays: &[(u16, u16)], plays: Option<u32>) -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n".to_vec();
+        png_chunk(&mut data, b"IHDR", &[0; 13]);
+        if let Some(plays) = plays {
+            let mut control = (delays.len() as u32).to_be_bytes().to_vec();
+            control.extend(plays.to_be_bytes());
+            png_chunk(&mut data, b"acTL", &control);
+        }
+        for (num, den) in delays {
+            let mut frame = vec![0; 20];
+            frame.extend(num.to_be_bytes());
+            frame.extend(den.to_be_bytes());
+            frame.extend([0, 0]);
+            png_chunk(&mut data, b"fcTL", &frame);
+            png_chunk(&mut data, b"IDAT", &[0; 3]);
+        }
+        png_chunk(&mut data, b"IEND", &[]);
+        data
+    }
+
+    /// An animated WebP container with one ANMF chunk per delay. Frame bitstreams are
+    /// filler of odd length, so every chunk needs its padding byte.
+    fn webp(delays_ms: &[u32], loop_count: u16) -> Vec<u8> {
+        let mut chunks = Vec::new();
+        let mut chunk = |kind: &[u8], body: &[u8]| {
+            chunks.extend(kind);
+            chunks.extend((body.len() as u32).to_le_bytes());
+            chunks.extend(body);
+            if body.len() % 2 == 1 {
+                chunks.push(0);
+            }
+        };
+        chunk(b"VP8X", &[0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let mut anim = vec![0; 4];
+        anim.extend(loop_count.to_le_bytes());
+        chunk(b"ANIM", &anim);
+        for delay in delays_ms {
+            let mut frame = vec![0; 12];
+            frame.extend(&delay.to_le_bytes()[..3]);
+            frame.extend([0, 0xaa, 0xbb]);
+            chunk(b"ANMF", &frame);
+        }
+
+        let mut data = b"RIFF".to_vec();
+        data.extend((chunks.len() as u32 + 4).to_le_bytes());
+        data.extend(b"WEBP");
+        data.extend(chunks);
+        data
+    }
+
+    /// (format, frames, loop count, duration in ms) of an animation.
+    fn summary(info: Option<AnimationInfo>) -> Option<(AnimationFormat, u32, u32, u64)> {
+        info.map(|i| {
+            (
+                i.format,
+                i.frame_count,
+                i.loop_count,
+                (i.duration * 1000.0).round() as u64,
+            )
+        })
+    }
+
+    #[test]
+    fn stretches_short_delays() {
+        let cases = [
+            (0, 100),
+            (10, 100),
+            (19, 100),
+            (20, 20),
+            (40, 40),
+            (1000, 1000),
+        ];
+        for (delay, expected) in cases {
+            assert_eq!(frame_delay_ms(delay), expected, "{} ms", delay);
+        }
+    }
+
+    #[test]
+    fn parses_gif_timing() {
+        use AnimationFormat::Gif;
+        let cases = [
+            (gif(&[10, 5, 1], Some(0)), Some((Gif, 3, 0, 250))),
+            (gif(&[4, 4], None), Some((Gif, 2, 1, 80))),
+            (gif(&[4, 4], Some(2)), Some((Gif, 2, 3, 80))),
+            (gif(&[10], Some(0)), None),
+        ];
+        for (data, expected) in cases {
+            assert_eq!(summary(parse_gif(&data)), expected);
+        }
+
+        // Cut off inside the last frame: the frames started so far still count
+        let data = gif(&[10, 10, 10], None);
+        assert_eq!(
+            summary(parse_gif(&data[..data.len() - 4])),
+            Some((Gif, 3, 1, 300))
+        );
+    }
+
+    #[test]
+    fn parses_apng_timing() {
+        use AnimationFormat::Apng;
+        let cases = [
+            (apng(&[(1, 10), (50, 0)], Some(0)), Some((Apng, 2, 0, 600))),
+            (
+                apng(&[(1, 30), (1, 30), (1, 30)], Some(2)),
+                Some((Apng, 3, 2, 100)),
+            ),
+            (apng(&[(1, 10)], Some(0)), None),
+            // A still PNG
+            (apng(&[], None), None),
+        ];
+        for (data, expected) in cases {
+            assert_eq!(summary(parse_apng(&data)), expected);
+        }
+    }
+
+    #[test]
+    fn parses_webp_timing() {
+        use AnimationFormat::WebP;
+        let cases = [
+            (webp(&[40, 0, 250], 3), Some((WebP, 3, 3, 390))),
+            (webp(&[100, 100], 0), Some((WebP, 2, 0, 200))),
+            (webp(&[100], 0), None),
+            (
+                b"RIFF\x0c\x00\x00\x00WEBPVP8L\x00\x00\x00\x00".to_vec(),
+                None,
+            ),
+        ];
+        for (data, expected) in cases {
+            assert_eq!(summary(parse_webp(&data)), expected);
+        }
+    }
+
+    #[test]
+    fn probes_only_animated_image_files() {
+        let dir = std::env::temp_dir();
+        let id = std::process::id();
+        let cases = [
+            ("gif", gif(&[10, 10], Some(0)), Some(AnimationFormat::Gif)),
+            // Sniffed from the data, not the extension
+            ("png", gif(&[10, 10], Some(0)), Some(AnimationFormat::Gif)),
+            (
+                "apng",
+                apng(&[(1, 10), (1, 10)], Some(0)),
+                Some(AnimationFormat::Apng),
+            ),
+            ("webp", webp(&[100, 100], 0), Some(AnimationFormat::WebP)),
+            ("jpg", gif(&[10, 10], Some(0)), None),
+            ("gif", b"not an image".to_vec(), None),
+        ];
+        for (i, (extension, data, expected)) in cases.into_iter().enumerate() {
+            let path = dir.join(format!("kenichi-animation-{}-{}.{}", id, i, extension));
+            std::fs::write(&path, data).unwrap();
+            let info = probe_animation(&path);
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(info.map(|i| i.format), expected, "{:?}", extension);
+        }
+    }
+}
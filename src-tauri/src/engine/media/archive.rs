@@ -0,0 +1,360 @@
+use super::super::types::ArchiveMediaEntry;
+use super::avio::MediaSource;
+use flate2::read::DeflateDecoder;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Take};
+use std::path::{Path, PathBuf};
+use zip::{CompressionMethod, ZipArchive};
+
+// Separates the archive from the entry inside it: `delivery.zip!/day1/clip.mov`
+const ENTRY_SEPARATOR: &str = "!/";
+
+// What the open dialog offers, plus the animated and sequence formats the engine plays
+const MEDIA_EXTENSIONS: &[&str] = &[
+    "mp4", "mkv", "avi", "mov", "webm", "mxf", "ts", "m2ts", "mp3", "wav", "flac", "m4a", "aac",
+    "ogg", "opus", "jpg", "jpeg", "png", "apng", "gif", "webp", "bmp", "tiff", "tif", "exr", "dpx",
+    "tga",
+];
+
+fn is_media_name(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| MEDIA_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// A file inside a ZIP archive, addressed as `archive.zip!/folder/clip.mov`.
+#[derive(Clone, Debug)]
+pub struct ArchiveEntry {
+    pub archive: PathBuf,
+    // Path inside the archive, '/'-separated
+    pub name: String,
+}
+
+impl ArchiveEntry {
+    /// Splits a path at the first `!/` following a `.zip`; `None` for ordinary paths.
+    pub fn parse(path: &Path) -> Option<Self> {
+        let path = path.to_str()?;
+        let split = path
+            .match_indices(ENTRY_SEPARATOR)
+            .map(|(i, _)| i)
+            .find(|&i| path[..i].to_ascii_lowercase().ends_with(".zip"))?;
+        let name = &path[split + ENTRY_SEPARATOR.len()..];
+        if name.is_empty() {
+            return None;
+        }
+        Some(Self {
+            archive: PathBuf::from(&path[..split]),
+            name: name.to_string(),
+        })
+    }
+
+    /// Streams the entry out of the archive. Stored entries seek directly; deflated ones
+    /// are decompressed from the start whenever a seek goes backwards.
+    pub fn open(&self) -> anyhow::Result<Box<dyn MediaSource>> {
+        let mut archive = ZipArchive::new(BufReader::new(File::open(&self.archive)?))?;
+        let (method, start, compressed_len, len) = {
+            let entry = archive.by_name(&self.name)?;
+            (
+                entry.compression(),
+                entry.data_start(),
+                entry.compressed_size(),
+                entry.size(),
+            )
+        };
+        let file = archive.into_inner().into_inner();
+        log::info!(
+            "[Archive] Opening {} from {:?} ({}, {} bytes)",
+            self.name,
+            self.archive,
+            method,
+            len
+        );
+        match method {
+            CompressionMethod::Stored => Ok(Box::new(StoredEntry::new(file, start, len)?)),
+            CompressionMethod::Deflated => Ok(Box::new(DeflatedEntry::new(
+                file,
+                start,
+                compressed_len,
+                len,
+            )?)),
+            other => anyhow::bail!(
+                "Unsupported compression {} for {} in {:?}",
+                other,
+                self.name,
+                self.archive
+            ),
+        }
+    }
+}
+
+/// Media files inside `archive`, in archive order. Directories and everything without a
+/// media extension are left out.
+pub fn list_archive_media(archive: &Path) -> anyhow::Result<Vec<ArchiveMediaEntry>> {
+    let mut zip = ZipArchive::new(BufReader::new(File::open(archive)?))?;
+    let mut entries = Vec::new();
+    for index in 0..zip.len() {
+        let entry = zip.by_index_raw(index)?;
+        if entry.is_dir() || !is_media_name(entry.name()) {
+            continue;
+        }
+        entries.push(ArchiveMediaEntry {
+            path: format!("{}{}{}", archive.display(), ENTRY_SEPARATOR, entry.name()),
+            name: entry.name().to_string(),
+            size: entry.size(),
+            seekable: entry.compression() == CompressionMethod::Stored,
+        });
+    }
+    Ok(entries)
+}
+
+/// New position after seeking `pos` within `len` bytes.
+fn seek_position(pos: u64, len: u64, from: SeekFrom) -> io::Result<u64> {
+    let target = match from {
+        SeekFrom::Start(offset) => Some(offset),
+        SeekFrom::Current(offset) => pos.checked_add_signed(offset),
+        SeekFrom::End(offset) => len.checked_add_signed(offset),
+    };
+    target.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek before start"))
+}
+
+/// An uncompressed entry: a window onto the archive file.
+struct StoredEntry {
+    file: File,
+    start: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl StoredEntry {
+    fn new(mut file: File, start: u64, len: u64) -> io::Result<Self> {
+        file.seek(SeekFrom::Start(start))?;
+        Ok(Self {
+            file,
+            start,
+            len,
+            pos: 0,
+        })
+    }
+}
+
+impl Read for StoredEntry {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.pos);
+        let max = buf.len().min(remaining as usize);
+        let n = self.file.read(&mut buf[..max])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for StoredEntry {
+    fn seek(&mut self, from: SeekFrom) -> io::Result<u64> {
+        self.pos = seek_position(self.pos, self.len, from)?;
+        self.file.seek(SeekFrom::Start(self.start + self.pos))?;
+        Ok(self.pos)
+    }
+}
+
+/// A deflated entry. Seeks only move `pos`; the next read decompresses up to it, from the
+/// start of the entry if it lies behind what has been decompressed.
+struct DeflatedEntry {
+    file: File,
+    start: u64,
+    compressed_len: u64,
+    len: u64,
+    // Position the caller asked for, and how far `decoder` has got
+    pos: u64,
+    decoded: u64,
+    decoder: DeflateDecoder<Take<BufReader<File>>>,
+}
+
+impl DeflatedEntry {
+    fn new(file: File, start: u64, compressed_len: u64, len: u64) -> io::Result<Self> {
+        let decoder = Self::decoder(&file, start, compressed_len)?;
+        Ok(Self {
+            file,
+            start,
+            compressed_len,
+            len,
+            pos: 0,
+            decoded: 0,
+            decoder,
+        })
+    }
+
+    fn decoder(
+        file: &File,
+        start: u64,
+        compressed_len: u64,
+    ) -> io::Result<DeflateDecoder<Take<BufReader<File>>>> {
+        let mut file = file.try_clone()?;
+        file.seek(SeekFrom::Start(start))?;
+        Ok(DeflateDecoder::new(
+            BufReader::new(file).take(compressed_len),
+        ))
+    }
+}
+
+impl Read for DeflatedEntry {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos < self.decoded {
+            log::debug!(
+                "[Archive] Seeking back to {} in a deflated entry, decompressing again",
+                self.pos
+            );
+            self.decoder = Self::decoder(&self.file, self.start, self.compressed_len)?;
+            self.decoded = 0;
+        }
+        if self.pos > self.decoded {
+            let skip = self.pos - self.decoded;
+            let skipped = io::copy(&mut (&mut self.decoder).take(skip), &mut io::sink())?;
+            self.decoded += skipped;
+            if skipped < skip {
+                // Past the end
+                return Ok(0);
+            }
+        }
+        let n = self.decoder.read(buf)?;
+        self.decoded += n as u64;
+        self.pos = self.decoded;
+        Ok(n)
+    }
+}
+
+impl Seek for DeflatedEntry {
+    fn seek(&mut self, from: SeekFrom) -> io::Result<u64> {
+        self.pos = seek_position(self.pos, self.len, from)?;
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::FileOptions;
+
+    fn sample_data() -> Vec<u8> {
+        (0..300_000u32).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    /// Writes a ZIP with one stored and one deflated copy of `sample_data`, plus a
+    /// non-media file, to the temp folder.
+    fn write_archive(test: &str) -> PathBuf {
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        let data = sample_data();
+        for (name, method) in [
+            ("cam_a/stored.mov", CompressionMethod::Stored),
+            ("cam_b/deflated.mp4", CompressionMethod::Deflated),
+            ("notes.txt", CompressionMethod::Deflated),
+        ] {
+            let options = FileOptions::default().compression_method(method);
+            zip.start_file(name, options).unwrap();
+            zip.write_all(&data).unwrap();
+        }
+        zip.add_directory("empty.mov/", FileOptions::default())
+            .unwrap();
+        let bytes = zip.finish().unwrap().into_inner();
+
+        let path = std::env::temp_dir().join(format!(
+            "kenichi-archive-{}-{}.zip",
+            test,
+            std::process::id()
+        ));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn open_entry(archive: &Path, name: &str) -> Box<dyn MediaSource> {
+        ArchiveEntry {
+            archive: archive.to_path_buf(),
+            name: name.to_string(),
+        }
+        .open()
+        .unwrap()
+    }
+
+    fn read_at(source: &mut dyn MediaSource, from: SeekFrom, len: usize) -> Vec<u8> {
+        source.seek(from).unwrap();
+        let mut buf = vec![0; len];
+        source.read_exact(&mut buf).unwrap();
+        buf
+    }
+
+    fn check_entry(source: &mut dyn MediaSource) {
+        let data = sample_data();
+        assert_eq!(source.seek(SeekFrom::End(0)).unwrap(), data.len() as u64);
+
+        // Forward, then backward from there
+        assert_eq!(
+            read_at(source, SeekFrom::Start(1000), 5000),
+            &data[1000..6000]
+        );
+        assert_eq!(
+            read_at(source, SeekFrom::Current(-3000), 5000),
+            &data[3000..8000]
+        );
+        assert_eq!(read_at(source, SeekFrom::Start(10), 100), &data[10..110]);
+        assert_eq!(
+            read_at(source, SeekFrom::Start(250_000), 1000),
+            &data[250_000..251_000]
+        );
+
+        // The tail, then past the end
+        let mut tail = Vec::new();
+        source.seek(SeekFrom::End(-100)).unwrap();
+        source.read_to_end(&mut tail).unwrap();
+        assert_eq!(tail, &data[data.len() - 100..]);
+        source.seek(SeekFrom::Start(data.len() as u64 + 10)).unwrap();
+        let mut buf = [0; 16];
+        assert_eq!(source.read(&mut buf).unwrap(), 0);
+        assert!(source.seek(SeekFrom::Current(-1_000_000)).is_err());
+
+        // And back to the start after all that
+        assert_eq!(read_at(source, SeekFrom::Start(0), 64), &data[..64]);
+    }
+
+    #[test]
+    fn stored_entry_reads_and_seeks() {
+        let archive = write_archive("stored");
+        check_entry(open_entry(&archive, "cam_a/stored.mov").as_mut());
+        std::fs::remove_file(archive).unwrap();
+    }
+
+    #[test]
+    fn deflated_entry_reads_and_seeks() {
+        let archive = write_archive("deflated");
+        check_entry(open_entry(&archive, "cam_b/deflated.mp4").as_mut());
+        std::fs::remove_file(archive).unwrap();
+    }
+
+    #[test]
+    fn lists_only_media_files() {
+        let archive = write_archive("list");
+        let entries = list_archive_media(&archive).unwrap();
+        let names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["cam_a/stored.mov", "cam_b/deflated.mp4"]);
+        assert!(entries[0].seekable);
+        assert!(!entries[1].seekable);
+        assert!(ArchiveEntry::parse(Path::new(&entries[1].path)).is_some());
+        std::fs::remove_file(archive).unwrap();
+    }
+
+    #[test]
+    fn parses_entry_paths() {
+        let entry = ArchiveEntry::parse(Path::new("a.zip!/b.mov")).unwrap();
+        assert_eq!(entry.archive, Path::new("a.zip"));
+        assert_eq!(entry.name, "b.mov");
+
+        let entry = ArchiveEntry::parse(Path::new("/deliveries/Day1.ZIP!/cam!/clip.mov")).unwrap();
+        assert_eq!(entry.archive, Path::new("/deliveries/Day1.ZIP"));
+        assert_eq!(entry.name, "cam!/clip.mov");
+
+        // No entry after the separator
+        assert!(ArchiveEntry::parse(Path::new("a.zip!/")).is_none());
+        // Separator not following a .zip
+        assert!(ArchiveEntry::parse(Path::new("renders!/b.mov")).is_none());
+        assert!(ArchiveEntry::parse(Path::new("/media/clip.mov")).is_none());
+    }
+}
@@ -0,0 +1,240 @@
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::ffi;
+use std::ffi::{c_int, c_void, CString};
+use std::io::{Read, Seek, SeekFrom};
+
+// Read buffer handed to FFmpeg; it reads through this in chunks of the same size
+const AVIO_BUFFER_SIZE: usize = 64 * 1024;
+
+const AVSEEK_SIZE: c_int = ffi::AVSEEK_SIZE as c_int;
+const AVSEEK_FORCE: c_int = ffi::AVSEEK_FORCE as c_int;
+// stdio's whence values, which AVIO passes through
+const SEEK_SET: c_int = 0;
+const SEEK_CUR: c_int = 1;
+const SEEK_END: c_int = 2;
+
+/// Anything media can be demuxed from: in-memory buffers, archive entries, decrypting readers.
+pub trait MediaSource: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> MediaSource for T {}
+
+/// AVIO context reading from a `MediaSource`. It must outlive the format context using it,
+/// which leaves custom IO alone when closed.
+pub struct AvioInput {
+    context: *mut ffi::AVIOContext,
+    // The callbacks' opaque pointer
+    source: *mut Box<dyn MediaSource>,
+}
+
+// The source is Send, and the context is only touched through the format context owning it
+unsafe impl Send for AvioInput {}
+
+unsafe extern "C" fn read_packet(opaque: *mut c_void, buf: *mut u8, buf_size: c_int) -> c_int {
+    let source = &mut *(opaque as *mut Box<dyn MediaSource>);
+    let buf = std::slice::from_raw_parts_mut(buf, buf_size.max(0) as usize);
+    loop {
+        match source.read(buf) {
+            Ok(0) => return ffi::AVERROR_EOF,
+            Ok(n) => return n as c_int,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                log::error!("[Avio] Read failed: {}", e);
+                return ffi::AVERROR(ffmpeg::util::error::EIO);
+            }
+        }
+    }
+}
+
+unsafe extern "C" fn seek(opaque: *mut c_void, offset: i64, whence: c_int) -> i64 {
+    let source = &mut *(opaque as *mut Box<dyn MediaSource>);
+    let result = match whence & !AVSEEK_FORCE {
+        AVSEEK_SIZE => stream_len(source.as_mut()),
+        SEEK_SET => source.seek(SeekFrom::Start(offset.max(0) as u64)),
+        SEEK_CUR => source.seek(SeekFrom::Current(offset)),
+        SEEK_END => source.seek(SeekFrom::End(offset)),
+        _ => return ffi::AVERROR(ffmpeg::util::error::EINVAL) as i64,
+    };
+    match result {
+        Ok(position) => position as i64,
+        Err(e) => {
+            log::error!("[Avio] Seek failed: {}", e);
+            ffi::AVERROR(ffmpeg::util::error::EIO) as i64
+        }
+    }
+}
+
+/// Total size of `source`, leaving its position where it was.
+fn stream_len(source: &mut dyn MediaSource) -> std::io::Result<u64> {
+    let position = source.stream_position()?;
+    let len = source.seek(SeekFrom::End(0))?;
+    source.seek(SeekFrom::Start(position))?;
+    Ok(len)
+}
+
+impl AvioInput {
+    /// Opens `source` as an input. `name` stands in for the file name: FFmpeg uses its
+    /// extension to help pick the demuxer, and it shows up in logs.
+    pub fn open(
+        source: Box<dyn MediaSource>,
+        name: &str,
+    ) -> anyhow::Result<(ffmpeg::format::context::Input, Self)> {
+        ffmpeg::init()?;
+        let url = CString::new(name)?;
+        let io = Self::new(source)?;
+        unsafe {
+            let mut format_ctx = ffi::avformat_alloc_context();
+            if format_ctx.is_null() {
+                anyhow::bail!("FFmpeg input error: out of memory");
+            }
+            (*format_ctx).pb = io.context;
+            (*format_ctx).flags |= ffi::AVFMT_FLAG_CUSTOM_IO as c_int;
+
+            // Frees the format context itself on failure
+            let ret = ffi::avformat_open_input(
+                &mut format_ctx,
+                url.as_ptr(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            );
+            if ret < 0 {
+                let e = ffmpeg::Error::from(ret);
+                log::error!("[Avio] Failed to open {}: {}", name, e);
+                anyhow::bail!("FFmpeg input error: {}", e);
+            }
+            let ret = ffi::avformat_find_stream_info(format_ctx, std::ptr::null_mut());
+            if ret < 0 {
+                ffi::avformat_close_input(&mut format_ctx);
+                let e = ffmpeg::Error::from(ret);
+                log::error!("[Avio] No stream info in {}: {}", name, e);
+                anyhow::bail!("FFmpeg input error: {}", e);
+            }
+            Ok((ffmpeg::format::context::Input::wrap(format_ctx), io))
+        }
+    }
+
+    fn new(source: Box<dyn MediaSource>) -> anyhow::Result<Self> {
+        let source = Box::into_raw(Box::new(source));
+        unsafe {
+            let buffer = ffi::av_malloc(AVIO_BUFFER_SIZE) as *mut u8;
+            let context = if buffer.is_null() {
+                std::ptr::null_mut()
+            } else {
+                ffi::avio_alloc_context(
+                    buffer,
+                    AVIO_BUFFER_SIZE as c_int,
+                    0,
+                    source as *mut c_void,
+                    Some(read_packet),
+                    None,
+                    Some(seek),
+                )
+            };
+            if context.is_null() {
+                ffi::av_free(buffer as *mut c_void);
+                drop(Box::from_raw(source));
+                anyhow::bail!("FFmpeg input error: out of memory");
+            }
+            Ok(Self { context, source })
+        }
+    }
+}
+
+impl Drop for AvioInput {
+    fn drop(&mut self) {
+        unsafe {
+            // FFmpeg may have swapped the buffer for a bigger one, so free whatever it holds now
+            ffi::av_freep(&mut (*self.context).buffer as *mut *mut u8 as *mut c_void);
+            ffi::avio_context_free(&mut self.context);
+            drop(Box::from_raw(self.source));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const SAMPLE_RATE: u32 = 8000;
+    const DATA_LEN: u32 = SAMPLE_RATE * 2;
+
+    /// One second of 16-bit mono PCM at 8 kHz as a WAV file.
+    fn wav_bytes() -> Vec<u8> {
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + DATA_LEN).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+        wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+        wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&DATA_LEN.to_le_bytes());
+        wav.extend((0..DATA_LEN).map(|i| (i % 251) as u8));
+        wav
+    }
+
+    // Field order frees the input before the IO it reads from
+    struct Opened {
+        input: ffmpeg::format::context::Input,
+        _io: AvioInput,
+    }
+
+    fn open_wav() -> Opened {
+        let (input, io) =
+            AvioInput::open(Box::new(Cursor::new(wav_bytes())), "memory.wav").unwrap();
+        Opened { input, _io: io }
+    }
+
+    #[test]
+    fn reads_every_packet_from_memory() {
+        let mut opened = open_wav();
+        assert_eq!(opened.input.format().name(), "wav");
+        let bytes: usize = opened
+            .input
+            .packets()
+            .map(|(_, packet)| packet.size())
+            .sum();
+        assert_eq!(bytes, DATA_LEN as usize);
+    }
+
+    #[test]
+    fn seeks_within_memory() {
+        let mut opened = open_wav();
+        // Half a second in, on the AV_TIME_BASE clock
+        opened.input.seek(500_000, ..500_000).unwrap();
+        let (stream, packet) = opened.input.packets().next().unwrap();
+        let pts = packet.pts().unwrap();
+        assert_eq!(stream.time_base(), ffmpeg::Rational(1, SAMPLE_RATE as i32));
+        assert!(pts > 0 && pts <= SAMPLE_RATE as i64 / 2, "landed at {}", pts);
+    }
+
+    #[test]
+    fn size_query_restores_position() {
+        let mut source: Box<dyn MediaSource> = Box::new(Cursor::new(vec![0u8; 100]));
+        source.seek(SeekFrom::Start(30)).unwrap();
+        let opaque = &mut source as *mut Box<dyn MediaSource> as *mut c_void;
+        assert_eq!(unsafe { seek(opaque, 0, AVSEEK_SIZE) }, 100);
+        assert_eq!(source.stream_position().unwrap(), 30);
+        assert_eq!(stream_len(source.as_mut()).unwrap(), 100);
+        assert_eq!(source.stream_position().unwrap(), 30);
+    }
+
+    #[test]
+    fn reports_end_of_source() {
+        let mut source: Box<dyn MediaSource> = Box::new(Cursor::new(vec![7u8; 10]));
+        let opaque = &mut source as *mut Box<dyn MediaSource> as *mut c_void;
+        let mut buf = [0u8; 16];
+        assert_eq!(unsafe { read_packet(opaque, buf.as_mut_ptr(), 16) }, 10);
+        assert_eq!(&buf[..10], &[7u8; 10]);
+        assert_eq!(
+            unsafe { read_packet(opaque, buf.as_mut_ptr(), 16) },
+            ffi::AVERROR_EOF
+        );
+        assert_eq!(unsafe { seek(opaque, 4, SEEK_SET) }, 4);
+        assert_eq!(unsafe { read_packet(opaque, buf.as_mut_ptr(), 16) }, 6);
+    }
+}
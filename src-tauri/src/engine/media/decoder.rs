@@ -1,23 +1,69 @@
-use super::super::types::QualityMode;
+use super::super::types::{
+    AnimationFormat, AnimationInfo, AudioOutputFormat, Chapter, ColorInfo, ColorMatrix, ColorRange,
+    DeinterlaceMode, DownmixMode, FramePlane, HdrMetadata, PlaneLayout, QualityMode,
+    ResampleQuality, SeekMode, StreamInfo, SubtitleCue, TransferFunction, VideoFilterFailure,
+    VideoFrame,
+};
+use super::animation::probe_animation;
+use super::avio::{AvioInput, MediaSource};
+use super::filter::VideoFilter;
+use super::hdr::{frame_hdr_metadata, stream_hdr_metadata, transfer_function};
+use super::probe::lookup_name;
+use super::resampler::AudioConverter;
+use super::sequence::ImageSequence;
+use super::streams::{channel_layout, describe_streams};
+use super::subtitles::SubtitleTrack;
+use super::timeline::{StreamClock, TimelineMap};
+use super::webp::WebpAnimation;
 use ffmpeg_next as ffmpeg;
-use ffmpeg_next::format::{input, Pixel};
+use ffmpeg_next::codec::packet::side_data::Type as SideDataType;
+use ffmpeg_next::color;
+use ffmpeg_next::format::stream::Disposition;
+use ffmpeg_next::format::Pixel;
 use ffmpeg_next::media::Type;
 use ffmpeg_next::software::scaling::{context::Context, flag::Flags};
 use ffmpeg_next::util::frame::audio::Audio;
 use ffmpeg_next::util::frame::video::Video;
+use std::collections::VecDeque;
 use std::path::Path;
 
+// Frames within this distance of a seek target count as having reached it (float rounding)
+const SEEK_TOLERANCE_SECS: f64 = 0.001;
+
+/// Where a `Decoder` reads from.
+pub enum MediaInput<'a> {
+    File(&'a Path),
+    Sequence(&'a ImageSequence),
+    // Read through a custom AVIO context; `name` stands in for the file name
+    Source {
+        name: &'a str,
+        source: Box<dyn MediaSource>,
+    },
+}
+
+impl MediaInput<'_> {
+    fn name(&self) -> String {
+        match self {
+            MediaInput::File(path) => path.display().to_string(),
+            MediaInput::Sequence(sequence) => sequence.pattern(),
+            MediaInput::Source { name, .. } => name.to_string(),
+        }
+    }
+}
+
 pub enum DecodeResult {
     Video {
-        data: Vec<u8>,
-        width: u32,
-        height: u32,
-        stride: u32,
+        frame: VideoFrame,
         pts: f64,
+        // Clockwise degrees (0/90/180/270) to show the frame upright
+        rotation: u32,
+        // Sample aspect ratio: display width of one pixel relative to its height
+        pixel_aspect: f32,
     },
     Audio {
         pts: f64,
     },
+    Subtitle(SubtitleCue),
 }
 
 pub struct Decoder {
@@ -26,141 +72,508 @@ pub struct Decoder {
     audio_stream_index: Option<usize>,
     decoder: Option<ffmpeg::decoder::Video>,
     audio_decoder: Option<ffmpeg::decoder::Audio>,
+    // Built on demand for frames the GPU can't take as decoded
     scaler: Option<Context>,
+    // Matrix and range the scaler's YUV to RGB conversion is set up for
+    scaler_color: Option<(ColorMatrix, ColorRange)>,
+    subtitle: Option<SubtitleTrack>,
     // Metadata
     duration_secs: f64,
-    time_base: ffmpeg::util::rational::Rational,
-    _audio_time_base: ffmpeg::util::rational::Rational,
+    // End of the furthest audio or video packet demuxed so far
+    demuxed_secs: f64,
+    quality: QualityMode,
+    // Timeline: container start time and per-stream timestamp normalisation
+    origin_secs: f64,
+    allow_discontinuities: bool,
+    // Discontinuities found so far, shared by the stream clocks
+    timeline: TimelineMap,
+    video_clock: StreamClock,
+    audio_clock: StreamClock,
     raw_frame: Video,
+    filtered_frame: Video,
     scaled_frame: Video,
+    video_filter: VideoFilter,
     audio_frame: Audio,
-    // Audio Buffer (Interleaved samples)
+    audio_converter: AudioConverter,
+    // Audio Buffer (Interleaved samples at the output device's rate and channel count)
     pub audio_buffer: Vec<f32>,
     audio_pts_counter: u64,
+    // The counter is re-anchored to the first timestamped audio frame after open/seek
+    audio_counter_anchored: bool,
+    // Precise Seek: frames/samples before these targets are decoded and dropped
+    video_seek_target: Option<f64>,
+    audio_seek_target: Option<f64>,
+    // Frames already decoded but not yet handed out (a packet can yield several)
+    pending: VecDeque<DecodeResult>,
+    last_video_pts: i64,
+    eof_reached: bool,
+    // Display geometry of the active video stream
+    video_rotation: u32,
+    video_sar: Option<f32>,
+    // Container HDR metadata, updated from whatever the frames carry
+    video_hdr: HdrMetadata,
+    // Sample aspect ratio of the latest decoded frame (before filtering)
+    pixel_aspect: f32,
+    // Colourspace of the latest queued frame
+    colorspace: Option<ColorInfo>,
+    // Whether the renderer takes 16-bit planes; otherwise everything is cut to 8 bits
+    high_bit_depth: bool,
+    // Animated image timing, and the frame source for animated WebP
+    animation: Option<AnimationInfo>,
+    webp: Option<WebpAnimation>,
+    // Growing file: end of data is reported without draining, and reading retries
+    follow: bool,
+    // Last packet read in follow mode as (stream, dts); a read cut short resumes after it
+    follow_resume: Option<(usize, i64)>,
+    // After resuming, packets up to and including this one were already handled
+    follow_skip: Option<(usize, i64)>,
+    // Custom IO behind `input_ctx`; declared after it so it is freed last
+    _avio: Option<AvioInput>,
+}
+
+/// Clockwise rotation (0, 90, 180 or 270) that shows the stream upright, from its display
+/// matrix side data or the legacy `rotate` tag.
+fn stream_rotation(stream: &ffmpeg::format::stream::Stream) -> u32 {
+    let from_matrix = stream
+        .side_data()
+        .find(|sd| sd.kind() == SideDataType::DisplayMatrix && sd.data().len() >= 36)
+        // The matrix angle is counter-clockwise
+        .map(|sd| unsafe {
+            -ffmpeg::ffi::av_display_rotation_get(sd.data().as_ptr() as *const i32)
+        });
+    let degrees = from_matrix
+        .or_else(|| {
+            stream
+                .metadata()
+                .get("rotate")
+                .and_then(|r| r.parse::<f64>().ok())
+        })
+        .unwrap_or(0.0);
+    if !degrees.is_finite() {
+        return 0;
+    }
+    ((degrees / 90.0).round() as i64 * 90).rem_euclid(360) as u32
+}
+
+/// The container's sample aspect ratio for the stream, if it declares a valid one.
+fn stream_sar(stream: &ffmpeg::format::stream::Stream) -> Option<f32> {
+    let sar = unsafe { (*stream.as_ptr()).sample_aspect_ratio };
+    (sar.num > 0 && sar.den > 0).then(|| sar.num as f32 / sar.den as f32)
+}
+
+fn stream_clock(
+    stream: &ffmpeg::format::stream::Stream,
+    origin_secs: f64,
+    allow_discontinuities: bool,
+    timeline: &TimelineMap,
+) -> StreamClock {
+    let wrap_bits = unsafe { (*stream.as_ptr()).pts_wrap_bits };
+    StreamClock::new(
+        stream.time_base(),
+        origin_secs,
+        wrap_bits,
+        allow_discontinuities,
+        timeline.clone(),
+    )
+}
+
+/// RGBA output size for a `width`x`height` picture at `quality`.
+fn target_size(quality: QualityMode, width: u32, height: u32) -> (u32, u32) {
+    match quality {
+        QualityMode::Native => (width, height),
+        QualityMode::Fast => (width / 2, height / 2),
+        QualityMode::Proxy => (width / 4, height / 4),
+    }
+}
+
+/// Opens a decoder for a video stream.
+fn open_video_stream(
+    stream: &ffmpeg::format::stream::Stream,
+) -> anyhow::Result<ffmpeg::decoder::Video> {
+    let context_decoder = ffmpeg::codec::context::Context::from_parameters(stream.parameters())?;
+    // FFmpeg's own VP9 decoder ignores the alpha layer WebM stores alongside; libvpx reads it
+    let alpha_codec = if context_decoder.id() == ffmpeg::codec::Id::VP9
+        && stream.metadata().get("alpha_mode") == Some("1")
+    {
+        ffmpeg::decoder::find_by_name("libvpx-vp9")
+    } else {
+        None
+    };
+    let opened = match alpha_codec {
+        Some(codec) => {
+            log::info!("[Decoder] VP9 stream has alpha, decoding with libvpx-vp9");
+            context_decoder
+                .decoder()
+                .open_as(codec)
+                .and_then(|o| o.video())
+        }
+        None => context_decoder.decoder().video(),
+    };
+    let ad = opened.map_err(|e| {
+        eprintln!("[Decoder] Failed to open video decoder: {}", e);
+        e
+    })?;
+    Ok(ad)
+}
+
+/// GPU plane layout for frames of `format`, or `None` when they need converting to RGBA.
+/// 16-bit layouts are only used when the renderer can sample 16-bit textures.
+fn plane_layout(format: Pixel, high_bit_depth: bool) -> Option<PlaneLayout> {
+    match format {
+        Pixel::YUV420P | Pixel::YUVJ420P => Some(PlaneLayout::Yuv420p),
+        Pixel::YUV422P | Pixel::YUVJ422P => Some(PlaneLayout::Yuv422p),
+        Pixel::NV12 => Some(PlaneLayout::Nv12),
+        Pixel::YUV420P10LE | Pixel::YUV420P12LE | Pixel::YUV420P16LE if high_bit_depth => {
+            Some(PlaneLayout::Yuv420p16)
+        }
+        Pixel::YUV422P10LE | Pixel::YUV422P12LE | Pixel::YUV422P16LE if high_bit_depth => {
+            Some(PlaneLayout::Yuv422p16)
+        }
+        Pixel::P010LE | Pixel::P016LE if high_bit_depth => Some(PlaneLayout::P010),
+        _ => None,
+    }
+}
+
+/// Whether `format` has an alpha channel.
+fn has_alpha(format: Pixel) -> bool {
+    unsafe {
+        let desc = ffmpeg::ffi::av_pix_fmt_desc_get(format.into());
+        !desc.is_null() && ((*desc).flags & ffmpeg::ffi::AV_PIX_FMT_FLAG_ALPHA as u64) != 0
+    }
+}
+
+/// Bits per component of `format` (e.g. 10 for P010 or yuv422p10), 8 when unknown.
+fn component_depth(format: Pixel) -> u32 {
+    unsafe {
+        let desc = ffmpeg::ffi::av_pix_fmt_desc_get(format.into());
+        if desc.is_null() || (*desc).nb_components == 0 {
+            8
+        } else {
+            (*desc).comp[0].depth as u32
+        }
+    }
+}
+
+/// Colour matrix and range of `frame`, plus whether the matrix was guessed. An unflagged
+/// matrix is taken as BT.709 for HD sizes and BT.601 below, as most players do.
+fn frame_color(frame: &Video) -> (ColorMatrix, bool, ColorRange) {
+    let range = if matches!(
+        frame.format(),
+        Pixel::YUVJ420P | Pixel::YUVJ422P | Pixel::YUVJ444P | Pixel::YUVJ440P
+    ) || frame.color_range() == color::Range::JPEG
+    {
+        ColorRange::Full
+    } else {
+        ColorRange::Limited
+    };
+
+    let is_rgb = unsafe {
+        let desc = ffmpeg::ffi::av_pix_fmt_desc_get(frame.format().into());
+        !desc.is_null() && ((*desc).flags & ffmpeg::ffi::AV_PIX_FMT_FLAG_RGB as u64) != 0
+    };
+    if is_rgb {
+        return (ColorMatrix::Rgb, false, ColorRange::Full);
+    }
+
+    let matrix = match frame.color_space() {
+        color::Space::RGB => Some(ColorMatrix::Rgb),
+        color::Space::BT709 => Some(ColorMatrix::Bt709),
+        color::Space::BT470BG | color::Space::SMPTE170M | color::Space::FCC => {
+            Some(ColorMatrix::Bt601)
+        }
+        color::Space::BT2020NCL | color::Space::BT2020CL => Some(ColorMatrix::Bt2020),
+        color::Space::SMPTE240M => Some(ColorMatrix::Smpte240m),
+        _ => None,
+    };
+    match matrix {
+        Some(matrix) => (matrix, false, range),
+        None if frame.width() >= 1280 || frame.height() > 576 => (ColorMatrix::Bt709, true, range),
+        None => (ColorMatrix::Bt601, true, range),
+    }
+}
+
+/// Points swscale's YUV to RGB conversion at `matrix` and `range`; left alone it assumes
+/// limited-range BT.601 whatever the source.
+fn set_scaler_colorspace(scaler: &mut Context, matrix: ColorMatrix, range: ColorRange) {
+    let colorspace = match matrix {
+        ColorMatrix::Bt709 => ffmpeg::ffi::SWS_CS_ITU709,
+        ColorMatrix::Bt2020 => ffmpeg::ffi::SWS_CS_BT2020,
+        ColorMatrix::Smpte240m => ffmpeg::ffi::SWS_CS_SMPTE240M,
+        ColorMatrix::Rgb | ColorMatrix::Bt601 => ffmpeg::ffi::SWS_CS_ITU601,
+    };
+    unsafe {
+        let ctx = scaler.as_mut_ptr();
+        let mut inv_table = std::ptr::null_mut();
+        let mut src_range = 0;
+        let mut table = std::ptr::null_mut();
+        let mut dst_range = 0;
+        let (mut brightness, mut contrast, mut saturation) = (0, 0, 0);
+        let ret = ffmpeg::ffi::sws_getColorspaceDetails(
+            ctx,
+            &mut inv_table,
+            &mut src_range,
+            &mut table,
+            &mut dst_range,
+            &mut brightness,
+            &mut contrast,
+            &mut saturation,
+        );
+        if ret < 0 {
+            log::warn!("[Decoder] Scaler colourspace not adjustable for this conversion");
+            return;
+        }
+        let ret = ffmpeg::ffi::sws_setColorspaceDetails(
+            ctx,
+            ffmpeg::ffi::sws_getCoefficients(colorspace as i32),
+            (range == ColorRange::Full) as i32,
+            table,
+            dst_range,
+            brightness,
+            contrast,
+            saturation,
+        );
+        if ret < 0 {
+            log::warn!("[Decoder] Failed to set scaler colourspace {:?}", matrix);
+        }
+    }
+}
+
+/// Copies the planes of `frame` out for upload, or `None` if any is stored bottom-up.
+fn copy_planes(frame: &Video, layout: PlaneLayout) -> Option<Vec<FramePlane>> {
+    (0..layout.plane_count())
+        .map(|index| {
+            let stride = frame.stride(index) as i32;
+            if stride <= 0 {
+                return None;
+            }
+            Some(FramePlane {
+                data: frame.data(index).to_vec(),
+                stride: stride as u32,
+            })
+        })
+        .collect()
+}
+
+/// Opens a decoder for an audio stream.
+fn open_audio_stream(
+    stream: &ffmpeg::format::stream::Stream,
+) -> anyhow::Result<ffmpeg::decoder::Audio> {
+    let context = ffmpeg::codec::context::Context::from_parameters(stream.parameters())?;
+    let ad = context.decoder().audio().map_err(|e| {
+        eprintln!("[Decoder] Failed to open audio decoder: {}", e);
+        e
+    })?;
+
+    // Same layout the resampler and downmixer take from the frames. WAVs often leave it
+    // unspecified, and then it is the default for the channel count.
+    let (channels, src_layout) = channel_layout(unsafe { &(*ad.as_ptr()).ch_layout });
+
+    eprintln!(
+        "[Decoder] Audio: {:?} format, {}Hz, {:?} ({} channels)",
+        ad.format(),
+        ad.rate(),
+        src_layout,
+        channels
+    );
+
+    Ok(ad)
 }
 
 impl Decoder {
-    pub fn new(path: &Path, quality: QualityMode) -> anyhow::Result<Self> {
+    pub fn new(
+        input: MediaInput,
+        quality: QualityMode,
+        audio_output: &AudioOutputFormat,
+        downmix_mode: DownmixMode,
+        resample_quality: ResampleQuality,
+        deinterlace_mode: DeinterlaceMode,
+        high_bit_depth: bool,
+    ) -> anyhow::Result<Self> {
         log::info!(
-            "[Decoder] Opening media: {:?}, Quality: {:?}",
-            path,
+            "[Decoder] Opening media: {}, Quality: {:?}",
+            input.name(),
             quality
         );
         ffmpeg::init()?;
-        let input_ctx = input(&path).map_err(|e| {
-            log::error!("[Decoder] Failed to open input for {:?}: {}", path, e);
-            anyhow::anyhow!("FFmpeg input error: {}", e)
-        })?;
+        // Animated images are only recognised on disk, where their headers can be read
+        let mut path = None;
+        // Declared before the input so that an early return frees it after the input
+        let mut avio = None;
+        let input_ctx = match input {
+            MediaInput::File(file) => {
+                path = Some(file);
+                ffmpeg::format::input(&file).map_err(|e| {
+                    log::error!("[Decoder] Failed to open input for {:?}: {}", file, e);
+                    anyhow::anyhow!("FFmpeg input error: {}", e)
+                })?
+            }
+            MediaInput::Sequence(sequence) => sequence.open()?,
+            MediaInput::Source { name, source } => {
+                let (input_ctx, io) = AvioInput::open(source, name)?;
+                avio = Some(io);
+                input_ctx
+            }
+        };
+
+        // Timeline origin: the container start time (non-zero for MPEG-TS captures and
+        // MOV edit lists). Formats like MPEG-TS also flag that timestamps may jump.
+        let (origin_secs, allow_discontinuities) = unsafe {
+            let ctx = input_ctx.as_ptr();
+            let start = (*ctx).start_time;
+            let origin = if start == ffmpeg::ffi::AV_NOPTS_VALUE {
+                0.0
+            } else {
+                start as f64 / 1_000_000.0
+            };
+            let iformat = (*ctx).iformat;
+            let discont = !iformat.is_null()
+                && ((*iformat).flags & ffmpeg::ffi::AVFMT_TS_DISCONT as i32) != 0;
+            (origin, discont)
+        };
+        log::info!(
+            "[Decoder] Timeline origin {}s, discontinuities allowed: {}",
+            origin_secs,
+            allow_discontinuities
+        );
+
+        let timeline = TimelineMap::default();
 
         // Video Setup
         let video_stream = input_ctx.streams().best(Type::Video);
         let mut decoder = None;
-        let mut scaler = None;
         let mut video_stream_index = None;
-        let mut time_base = ffmpeg::util::rational::Rational(0, 1);
+        let mut video_clock = StreamClock::new(
+            ffmpeg::Rational(0, 1),
+            origin_secs,
+            0,
+            false,
+            timeline.clone(),
+        );
+        let mut video_rotation = 0;
+        let mut video_sar = None;
+        let mut video_hdr = HdrMetadata::default();
 
         if let Some(s) = video_stream {
             eprintln!("[Decoder] Found video stream at index {}", s.index());
-            let context_decoder = ffmpeg::codec::context::Context::from_parameters(s.parameters())?;
-            let ad = context_decoder.decoder().video().map_err(|e| {
-                eprintln!("[Decoder] Failed to open video decoder: {}", e);
-                e
-            })?;
-
-            let width = ad.width();
-            let height = ad.height();
-            let (target_width, target_height) = match quality {
-                QualityMode::Native => (width, height),
-                QualityMode::Fast => (width / 2, height / 2),
-                QualityMode::Proxy => (width / 4, height / 4),
-            };
-
-            scaler = Some(
-                Context::get(
-                    ad.format(),
-                    width,
-                    height,
-                    Pixel::RGBA,
-                    target_width,
-                    target_height,
-                    Flags::BILINEAR,
-                )
-                .map_err(|e| {
-                    eprintln!("[Decoder] Failed to initialize scaler: {}", e);
-                    e
-                })?,
-            );
-
+            let ad = open_video_stream(&s)?;
             video_stream_index = Some(s.index());
-            time_base = s.time_base();
+            video_clock = stream_clock(&s, origin_secs, allow_discontinuities, &timeline);
+            video_rotation = stream_rotation(&s);
+            video_sar = stream_sar(&s);
+            video_hdr = stream_hdr_metadata(&s);
+            log::info!(
+                "[Decoder] Video rotation {} degrees, container SAR {:?}",
+                video_rotation,
+                video_sar
+            );
             decoder = Some(ad);
         }
 
-        let duration_secs = input_ctx.duration() as f64 / 1_000_000.0;
+        let animation = path.and_then(probe_animation);
+        let webp = match (&animation, path) {
+            (Some(a), Some(path)) if a.format == AnimationFormat::WebP => {
+                Some(WebpAnimation::open(path)?)
+            }
+            _ => None,
+        };
+        // FFmpeg can't time what it can't decode
+        let duration_secs = match (&animation, &webp) {
+            (Some(a), Some(_)) => a.duration,
+            _ => input_ctx.duration() as f64 / 1_000_000.0,
+        };
         eprintln!("[Decoder] Media duration identified: {}s", duration_secs);
 
         // Audio Setup
         let audio_stream = input_ctx.streams().best(Type::Audio);
         let mut audio_decoder = None;
         let mut audio_stream_index = None;
-        let mut audio_time_base = ffmpeg::util::rational::Rational(0, 1);
+        let mut audio_clock = StreamClock::new(
+            ffmpeg::Rational(0, 1),
+            origin_secs,
+            0,
+            false,
+            timeline.clone(),
+        );
 
         if let Some(s) = audio_stream {
             eprintln!("[Decoder] Found audio stream at index {}", s.index());
-            let context = ffmpeg::codec::context::Context::from_parameters(s.parameters())?;
-            let ad = context.decoder().audio().map_err(|e| {
-                eprintln!("[Decoder] Failed to open audio decoder: {}", e);
-                e
-            })?;
+            audio_decoder = Some(open_audio_stream(&s)?);
             audio_stream_index = Some(s.index());
-            audio_time_base = s.time_base();
-
-            // Defensive Layout Check: WAVs often have unspecified layout in parameters
-            let src_layout = if ad.channel_layout().is_empty() {
-                let layout = ffmpeg::channel_layout::ChannelLayout::default(ad.channels() as i32);
-                eprintln!(
-                    "[Decoder] Audio layout unspecified, defaulting to {:?} based on {} channels",
-                    layout,
-                    ad.channels()
-                );
-                layout
-            } else {
-                ad.channel_layout()
-            };
-
-            eprintln!(
-                "[Decoder] Audio: {:?} format, {}Hz, {:?} ({} channels)",
-                ad.format(),
-                ad.rate(),
-                src_layout,
-                ad.channels()
-            );
-
-            audio_decoder = Some(ad);
+            audio_clock = stream_clock(&s, origin_secs, allow_discontinuities, &timeline);
         }
 
+        // Subtitle Setup: only tracks the file flags as default or forced start enabled
+        let subtitle = input_ctx
+            .streams()
+            .filter(|s| s.parameters().medium() == Type::Subtitle)
+            .find(|s| {
+                s.disposition()
+                    .intersects(Disposition::DEFAULT | Disposition::FORCED)
+            })
+            .and_then(|s| {
+                let clock = stream_clock(&s, origin_secs, allow_discontinuities, &timeline);
+                SubtitleTrack::open(&s, clock)
+                    .map_err(|e| log::warn!("[Decoder] Subtitles unavailable: {}", e))
+                    .ok()
+            });
+
         Ok(Self {
             input_ctx,
             video_stream_index,
             audio_stream_index,
             decoder,
             audio_decoder,
-            scaler,
+            scaler: None,
+            scaler_color: None,
+            subtitle,
             duration_secs,
-            time_base,
-            _audio_time_base: audio_time_base,
+            demuxed_secs: 0.0,
+            quality,
+            origin_secs,
+            allow_discontinuities,
+            timeline,
+            video_clock,
+            audio_clock,
             raw_frame: Video::empty(),
+            filtered_frame: Video::empty(),
             scaled_frame: Video::empty(),
+            video_filter: VideoFilter::new(deinterlace_mode),
             audio_frame: Audio::empty(),
+            audio_converter: AudioConverter::new(audio_output, downmix_mode, resample_quality),
             audio_buffer: Vec::with_capacity(4096),
             audio_pts_counter: 0,
+            audio_counter_anchored: false,
+            video_seek_target: None,
+            audio_seek_target: None,
+            pending: VecDeque::new(),
+            last_video_pts: 0,
+            eof_reached: false,
+            video_rotation,
+            video_sar,
+            video_hdr,
+            pixel_aspect: 1.0,
+            colorspace: None,
+            high_bit_depth,
+            animation,
+            webp,
+            follow: false,
+            follow_resume: None,
+            follow_skip: None,
+            _avio: avio,
         })
     }
 
-    pub fn seek(&mut self, time_secs: f64) -> anyhow::Result<()> {
-        let timestamp = (time_secs * 1_000_000.0) as i64;
-        // Seek to timestamp in microseconds (AV_TIME_BASE is 1,000,000)
-        self.input_ctx.seek(timestamp, ..timestamp)?;
+    /// Seeks to `time_secs`. `Keyframe` mode stops at the preceding keyframe, while
+    /// `Precise` mode makes `decode_next` drop every frame and audio sample before the target.
+    pub fn seek(&mut self, time_secs: f64, mode: SeekMode) -> anyhow::Result<()> {
+        // Seek to timestamp in microseconds (AV_TIME_BASE is 1,000,000), on the container's
+        // own clock: undo the origin and whatever corrections apply at the target
+        let raw_secs = self.timeline.raw_secs(time_secs, self.origin_secs);
+        let timestamp = (raw_secs * 1_000_000.0) as i64;
+        match self.webp.as_mut() {
+            Some(webp) => webp.seek(time_secs)?,
+            None => self.input_ctx.seek(timestamp, ..timestamp)?,
+        }
 
         // Flush internal buffers
         if let Some(ref mut d) = self.decoder {
@@ -169,14 +582,201 @@ impl Decoder {
         if let Some(ref mut ad) = self.audio_decoder {
             ad.flush();
         }
+        if let Some(ref mut st) = self.subtitle {
+            st.flush(time_secs);
+        }
+        self.video_filter.reset();
 
         // Reset buffers
+        self.pending.clear();
+        self.eof_reached = false;
         self.audio_buffer.clear();
-        self.audio_pts_counter = (time_secs * 48000.0) as u64; // Approx reset
+        self.audio_converter.reset();
+        self.audio_pts_counter = (time_secs * self.audio_converter.output_rate() as f64) as u64; // Approx reset
+        self.audio_counter_anchored = false;
+        self.video_clock.reset(time_secs);
+        self.audio_clock.reset(time_secs);
+        self.follow_resume = None;
+        self.follow_skip = None;
+
+        // Animated WebP seeks already land on the frame showing at the target
+        let precise = mode == SeekMode::Precise && self.webp.is_none();
+        self.video_seek_target = self
+            .video_stream_index
+            .filter(|_| precise)
+            .map(|_| time_secs);
+        self.audio_seek_target = self
+            .audio_stream_index
+            .filter(|_| precise)
+            .map(|_| time_secs);
+
+        Ok(())
+    }
+
+    /// Switches the surround fold-down matrix; takes effect from the next decoded frame.
+    pub fn set_downmix_mode(&mut self, mode: DownmixMode) {
+        self.audio_converter.set_downmix_mode(mode);
+    }
+
+    /// Switches the resampler quality preset; takes effect from the next decoded frame.
+    pub fn set_resample_quality(&mut self, quality: ResampleQuality) {
+        self.audio_converter.set_quality(quality);
+    }
+
+    /// Switches the deinterlace mode; takes effect from the next decoded frame.
+    pub fn set_deinterlace_mode(&mut self, mode: DeinterlaceMode) {
+        self.video_filter.set_deinterlace_mode(mode);
+    }
+
+    /// Sets the user filter chain applied before scaling; takes effect from the next
+    /// decoded frame and persists across seeks.
+    pub fn set_video_filter(&mut self, spec: Option<&str>) {
+        self.video_filter.set_user_filter(spec);
+    }
+
+    /// A filter chain that failed to build for the decoded frames since the last call.
+    pub fn take_filter_failure(&mut self) -> Option<VideoFilterFailure> {
+        self.video_filter.take_failure()
+    }
+
+    /// Colourspace of the most recent video frame.
+    pub fn colorspace(&self) -> Option<&ColorInfo> {
+        self.colorspace.as_ref()
+    }
+
+    /// Video frames produced per decoded frame (2 while bob deinterlacing).
+    pub fn video_rate_factor(&self) -> u32 {
+        self.video_filter.rate_factor()
+    }
+
+    /// Lists every stream in the container, flagging the ones currently being decoded.
+    pub fn streams(&self) -> Vec<StreamInfo> {
+        describe_streams(
+            &self.input_ctx,
+            self.video_stream_index,
+            self.audio_stream_index,
+            self.subtitle.as_ref().map(|st| st.index),
+        )
+    }
+
+    /// Switches video decoding to stream `index`. Callers should seek afterwards so the
+    /// new track resumes at the current position.
+    pub fn select_video_stream(&mut self, index: usize) -> anyhow::Result<()> {
+        let stream = self
+            .input_ctx
+            .stream(index)
+            .ok_or_else(|| anyhow::anyhow!("No stream at index {}", index))?;
+        if stream.parameters().medium() != Type::Video {
+            anyhow::bail!("Stream {} is not a video stream", index);
+        }
+
+        let decoder = open_video_stream(&stream)?;
+        self.video_clock = stream_clock(
+            &stream,
+            self.origin_secs,
+            self.allow_discontinuities,
+            &self.timeline,
+        );
+        self.video_rotation = stream_rotation(&stream);
+        self.video_sar = stream_sar(&stream);
+        self.video_hdr = stream_hdr_metadata(&stream);
+        self.decoder = Some(decoder);
+        self.scaler = None;
+        self.video_filter.reset();
+        self.video_stream_index = Some(index);
+        log::info!("[Decoder] Switched video to stream {}", index);
+        Ok(())
+    }
+
+    /// Switches audio decoding to stream `index`. Callers should seek afterwards so the
+    /// new track resumes at the current position.
+    pub fn select_audio_stream(&mut self, index: usize) -> anyhow::Result<()> {
+        let stream = self
+            .input_ctx
+            .stream(index)
+            .ok_or_else(|| anyhow::anyhow!("No stream at index {}", index))?;
+        if stream.parameters().medium() != Type::Audio {
+            anyhow::bail!("Stream {} is not an audio stream", index);
+        }
+
+        let audio_decoder = open_audio_stream(&stream)?;
+        self.audio_clock = stream_clock(
+            &stream,
+            self.origin_secs,
+            self.allow_discontinuities,
+            &self.timeline,
+        );
+        self.audio_decoder = Some(audio_decoder);
+        self.audio_stream_index = Some(index);
+        log::info!("[Decoder] Switched audio to stream {}", index);
+        Ok(())
+    }
+
+    /// Switches subtitle decoding to stream `index`, or turns it off with `None`.
+    /// Cues are only produced for packets read afterwards, so callers should seek to
+    /// pick up the cue already on screen.
+    pub fn select_subtitle_stream(&mut self, index: Option<usize>) -> anyhow::Result<()> {
+        let index = match index {
+            Some(index) => index,
+            None => {
+                self.subtitle = None;
+                log::info!("[Decoder] Subtitles off");
+                return Ok(());
+            }
+        };
+        let stream = self
+            .input_ctx
+            .stream(index)
+            .ok_or_else(|| anyhow::anyhow!("No stream at index {}", index))?;
+        if stream.parameters().medium() != Type::Subtitle {
+            anyhow::bail!("Stream {} is not a subtitle stream", index);
+        }
 
+        let clock = stream_clock(
+            &stream,
+            self.origin_secs,
+            self.allow_discontinuities,
+            &self.timeline,
+        );
+        self.subtitle = Some(SubtitleTrack::open(&stream, clock)?);
+        log::info!("[Decoder] Switched subtitles to stream {}", index);
         Ok(())
     }
 
+    /// Chapters from the container, on the zero-based timeline and in start order.
+    pub fn chapters(&self) -> Vec<Chapter> {
+        let mut chapters: Vec<Chapter> = self
+            .input_ctx
+            .chapters()
+            .map(|chapter| {
+                let tb = chapter.time_base();
+                let to_secs = |ts: i64| {
+                    if tb.1 == 0 {
+                        0.0
+                    } else {
+                        ts as f64 * tb.0 as f64 / tb.1 as f64 - self.origin_secs
+                    }
+                };
+                Chapter {
+                    index: 0,
+                    start: to_secs(chapter.start()).max(0.0),
+                    end: to_secs(chapter.end()).max(0.0),
+                    title: chapter.metadata().get("title").map(str::to_string),
+                }
+            })
+            .collect();
+        chapters.sort_by(|a, b| a.start.total_cmp(&b.start));
+        for (index, chapter) in chapters.iter_mut().enumerate() {
+            chapter.index = index;
+        }
+        chapters
+    }
+
+    /// Frame and loop counts when the file is an animated image.
+    pub fn animation(&self) -> Option<&AnimationInfo> {
+        self.animation.as_ref()
+    }
+
     pub fn get_metadata(&self) -> (f64, u32, u32) {
         let (w, h) = if let Some(ref d) = self.decoder {
             (d.width(), d.height())
@@ -186,189 +786,532 @@ impl Decoder {
         (self.duration_secs, w, h)
     }
 
+    /// Returns the next decoded frame. Every frame a packet yields is queued, and at end
+    /// of stream the decoders (and resampler) are drained before `Ok(None)` is returned.
     pub fn decode_next(&mut self) -> anyhow::Result<Option<DecodeResult>> {
-        for (stream, packet) in self.input_ctx.packets() {
-            let pts = packet.pts().unwrap_or(0);
+        loop {
+            if let Some(result) = self.pending.pop_front() {
+                return Ok(Some(result));
+            }
+            if self.eof_reached {
+                return Ok(None);
+            }
+            if self.webp.is_some() {
+                self.decode_webp_frame()?;
+                continue;
+            }
+
+            // Where this packet starts, to tell a clean end of a followed file from a cut
+            // short one
+            let packet_start = self.follow.then(|| self.io_position()).flatten();
+            let mut packet = ffmpeg::Packet::empty();
+            match packet.read(&mut self.input_ctx) {
+                Ok(()) => {}
+                // The tail of a file being written is usually a packet still incomplete
+                Err(e) if self.follow && (e == ffmpeg::Error::Eof || self.io_at_end()) => {
+                    if self.io_position() == packet_start {
+                        self.clear_io_eof();
+                    } else {
+                        log::debug!(
+                            "[Decoder] Partial packet at end of file ({:?}), resuming",
+                            e
+                        );
+                        self.resume_demuxer();
+                    }
+                    return Ok(None);
+                }
+                Err(ffmpeg::Error::Eof) => {
+                    self.drain_at_eof()?;
+                    continue;
+                }
+                Err(e) => {
+                    log::warn!("[Decoder] Packet read error: {:?} - skipping", e);
+                    continue;
+                }
+            }
+
+            if self.follow {
+                let read = packet
+                    .dts()
+                    .or(packet.pts())
+                    .map(|ts| (packet.stream(), ts));
+                if let Some((stream, skip_ts)) = self.follow_skip {
+                    if read.is_some_and(|(s, ts)| s == stream && ts >= skip_ts) {
+                        self.follow_skip = None;
+                    }
+                    // Up to the last packet handled before resuming, everything is a repeat
+                    if self.follow_skip.is_some() || read == Some((stream, skip_ts)) {
+                        continue;
+                    }
+                }
+                if read.is_some() {
+                    self.follow_resume = read;
+                }
+            }
+
+            let pts = packet.pts();
+            if let Some(ts) = pts {
+                let end = ts + packet.duration().max(0);
+                let clock = if Some(packet.stream()) == self.video_stream_index {
+                    Some(&self.video_clock)
+                } else if Some(packet.stream()) == self.audio_stream_index {
+                    Some(&self.audio_clock)
+                } else {
+                    None
+                };
+                if let Some(clock) = clock {
+                    self.demuxed_secs = self.demuxed_secs.max(clock.peek_secs(end));
+                }
+            }
 
-            if Some(stream.index()) == self.video_stream_index {
-                if let (Some(ref mut d), Some(ref mut s)) = (&mut self.decoder, &mut self.scaler) {
+            if Some(packet.stream()) == self.video_stream_index {
+                if let Some(ref mut d) = self.decoder {
                     if let Err(e) = d.send_packet(&packet) {
                         log::warn!("[Decoder] Video send_packet error: {:?} - continuing", e);
                         // Continue to try receive_frame anyway, or just next packet
                     }
-                    if d.receive_frame(&mut self.raw_frame).is_ok() {
-                        s.run(&self.raw_frame, &mut self.scaled_frame)?;
+                }
+                self.receive_video_frames(pts)?;
+            } else if Some(packet.stream()) == self.audio_stream_index {
+                if let Some(ref mut ad) = self.audio_decoder {
+                    if let Err(e) = ad.send_packet(&packet) {
+                        log::warn!(
+                            "[Decoder] Audio send_packet error: {:?} - ignoring and continuing",
+                            e
+                        );
+                    }
+                }
+                self.receive_audio_frames();
+            } else if Some(packet.stream()) == self.subtitle.as_ref().map(|st| st.index) {
+                let canvas = self
+                    .decoder
+                    .as_ref()
+                    .map_or((0, 0), |d| (d.width(), d.height()));
+                if let Some(st) = self.subtitle.as_mut() {
+                    // Cues are never dropped for a precise seek: one that started before
+                    // the target may still be on screen at it
+                    if let Some(cue) = st.decode(&packet, canvas) {
+                        self.pending.push_back(DecodeResult::Subtitle(cue));
+                    }
+                }
+            }
+        }
+    }
+
+    /// In follow mode `decode_next` returns `None` at the current end of the file but keeps
+    /// its frames and reads on from there next time, for files still being written.
+    pub fn set_follow(&mut self, follow: bool) {
+        if follow != self.follow {
+            // Packets read in between were not tracked
+            self.follow_resume = None;
+            self.follow_skip = None;
+        }
+        self.follow = follow;
+    }
+
+    /// Re-estimates the duration of a file still being written: how far demuxing has got,
+    /// or further if the file size at the container's bit rate says there is more.
+    pub fn estimate_duration(&mut self) -> f64 {
+        let from_size = unsafe {
+            let ctx = self.input_ctx.as_ptr();
+            let pb = (*ctx).pb;
+            let size = if pb.is_null() {
+                0
+            } else {
+                ffmpeg::ffi::avio_size(pb)
+            };
+            if size > 0 && (*ctx).bit_rate > 0 {
+                size as f64 * 8.0 / (*ctx).bit_rate as f64
+            } else {
+                0.0
+            }
+        };
+        self.duration_secs = self.duration_secs.max(self.demuxed_secs).max(from_size);
+        self.duration_secs
+    }
+
+    /// Byte position of the input's IO context, if it has one.
+    fn io_position(&self) -> Option<i64> {
+        unsafe {
+            let pb = (*self.input_ctx.as_ptr()).pb;
+            // SEEK_CUR with no offset only reports the position
+            (!pb.is_null()).then(|| ffmpeg::ffi::avio_seek(pb, 0, 1))
+        }
+    }
+
+    /// Whether reading has reached the end of what the file holds so far.
+    fn io_at_end(&self) -> bool {
+        unsafe {
+            let pb = (*self.input_ctx.as_ptr()).pb;
+            !pb.is_null()
+                && ((*pb).eof_reached != 0
+                    || ffmpeg::ffi::avio_seek(pb, 0, 1) >= ffmpeg::ffi::avio_size(pb))
+        }
+    }
+
+    /// Clears the IO end-of-file and error state, so the next read picks up data written
+    /// since from where the demuxer stopped.
+    fn clear_io_eof(&mut self) {
+        unsafe {
+            let pb = (*self.input_ctx.as_mut_ptr()).pb;
+            if !pb.is_null() {
+                (*pb).eof_reached = 0;
+                (*pb).error = 0;
+            }
+        }
+    }
+
+    /// Recovers from a packet cut short at the end of a followed file. The demuxer has
+    /// already parsed part of it, so it is sought back to the last packet read (which also
+    /// resets its parsers) and the packets up to it are skipped when read again.
+    fn resume_demuxer(&mut self) {
+        self.clear_io_eof();
+        let (stream, ts, flags) = match self.follow_resume {
+            Some((stream, ts)) => (stream as i32, ts, ffmpeg::ffi::AVSEEK_FLAG_BACKWARD as i32),
+            // Nothing read yet: the first keyframe, on the AV_TIME_BASE clock
+            None => (-1, 0, 0),
+        };
+        let ret =
+            unsafe { ffmpeg::ffi::av_seek_frame(self.input_ctx.as_mut_ptr(), stream, ts, flags) };
+        if ret < 0 {
+            log::warn!(
+                "[Decoder] Could not resume the followed file: {}",
+                ffmpeg::Error::from(ret)
+            );
+            return;
+        }
+        self.follow_skip = self.follow_resume;
+    }
+
+    /// Queues the next animated WebP frame, or marks the end of the animation.
+    fn decode_webp_frame(&mut self) -> anyhow::Result<()> {
+        let webp = match self.webp.as_mut() {
+            Some(webp) => webp,
+            None => return Ok(()),
+        };
+        let (width, height) = webp.dimensions();
+        if (
+            self.raw_frame.format(),
+            self.raw_frame.width(),
+            self.raw_frame.height(),
+        ) != (Pixel::RGBA, width, height)
+        {
+            self.raw_frame = Video::new(Pixel::RGBA, width, height);
+        }
+        match webp.next_frame(&mut self.raw_frame)? {
+            Some(pts_secs) => self.queue_video_frame(false, pts_secs),
+            None => {
+                self.eof_reached = true;
+                Ok(())
+            }
+        }
+    }
 
-                        let stride = self.scaled_frame.stride(0) as i32;
-                        let width = self.scaled_frame.width();
-                        let height = self.scaled_frame.height();
+    /// Signals end of stream to both decoders and queues every frame they still hold.
+    fn drain_at_eof(&mut self) -> anyhow::Result<()> {
+        log::info!("[Decoder] End of stream, draining decoders");
+        self.eof_reached = true;
 
-                        if stride <= 0 {
-                            continue;
-                        }
+        if let Some(ref mut d) = self.decoder {
+            if let Err(e) = d.send_eof() {
+                log::warn!("[Decoder] Video send_eof error: {:?}", e);
+            }
+        }
+        self.receive_video_frames(None)?;
+
+        // Frames the deinterlacer is still holding back
+        self.video_filter.flush();
+        while let Some(pts_secs) = self.video_filter.pull(&mut self.filtered_frame) {
+            self.queue_video_frame(true, pts_secs)?;
+        }
+
+        if let Some(ref mut ad) = self.audio_decoder {
+            if let Err(e) = ad.send_eof() {
+                log::warn!("[Decoder] Audio send_eof error: {:?}", e);
+            }
+        }
+        self.receive_audio_frames();
+
+        // Samples still inside the resampler's filter
+        match self.audio_converter.flush(&mut self.audio_buffer) {
+            Ok(samples) if samples > 0 => {
+                self.audio_pts_counter += samples as u64;
+                self.pending.push_back(DecodeResult::Audio {
+                    pts: self.audio_pts_counter as f64 / self.audio_converter.output_rate() as f64,
+                });
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("[Decoder] Resampler flush failed: {}", e),
+        }
+
+        Ok(())
+    }
+
+    /// Receives every frame the video decoder has ready, deinterlaces, scales and queues it.
+    /// `packet_pts` is used when a frame carries no timestamp of its own.
+    fn receive_video_frames(&mut self, packet_pts: Option<i64>) -> anyhow::Result<()> {
+        loop {
+            let received = match self.decoder.as_mut() {
+                Some(d) => d.receive_frame(&mut self.raw_frame).is_ok(),
+                None => false,
+            };
+            if !received {
+                break;
+            }
+
+            let frame_pts = self
+                .raw_frame
+                .timestamp()
+                .or(packet_pts)
+                .unwrap_or(self.last_video_pts);
+            self.last_video_pts = frame_pts;
+            let pts_secs = self.video_clock.to_secs(frame_pts);
+
+            // Container SAR overrides the codec's, as in av_guess_sample_aspect_ratio
+            let frame_sar = self.raw_frame.aspect_ratio();
+            self.pixel_aspect = self.video_sar.unwrap_or_else(|| {
+                if frame_sar.0 > 0 && frame_sar.1 > 0 {
+                    frame_sar.0 as f32 / frame_sar.1 as f32
+                } else {
+                    1.0
+                }
+            });
+            self.video_hdr = frame_hdr_metadata(&self.raw_frame).or(self.video_hdr);
+
+            if !self.video_filter.push(&mut self.raw_frame, pts_secs) {
+                self.queue_video_frame(false, pts_secs)?;
+                continue;
+            }
+            while let Some(pts_secs) = self.video_filter.pull(&mut self.filtered_frame) {
+                self.queue_video_frame(true, pts_secs)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Queues the raw (or, with `filtered`, the filtered) frame for display, unless a
+    /// precise seek has yet to reach its target. YUV the GPU can sample is handed over as
+    /// planes; anything else, or a frame that needs downscaling, goes through swscale.
+    fn queue_video_frame(&mut self, filtered: bool, pts_secs: f64) -> anyhow::Result<()> {
+        // Precise Seek: drop frames between the keyframe and the target
+        if let Some(target) = self.video_seek_target {
+            if pts_secs < target - SEEK_TOLERANCE_SECS {
+                return Ok(());
+            }
+            self.video_seek_target = None;
+        }
+
+        let frame = if filtered {
+            &self.filtered_frame
+        } else {
+            &self.raw_frame
+        };
+        let (width, height) = (frame.width(), frame.height());
+        let (target_width, target_height) = target_size(self.quality, width, height);
+        let (matrix, matrix_guessed, range) = frame_color(frame);
 
-                        let pts_secs =
-                            pts as f64 * (self.time_base.0 as f64 / self.time_base.1 as f64);
+        // Report the colourspace when it changes
+        let primaries = frame.color_primaries();
+        let transfer = frame.color_transfer_characteristic();
+        let (primaries_name, transfer_name) = unsafe {
+            (
+                lookup_name(ffmpeg::ffi::av_color_primaries_name(primaries.into())),
+                lookup_name(ffmpeg::ffi::av_color_transfer_name(transfer.into())),
+            )
+        };
+        let transfer_fn = transfer_function(frame);
+        let wide_gamut = primaries == color::Primaries::BT2020;
+        let colorspace = ColorInfo {
+            matrix,
+            matrix_guessed,
+            range,
+            primaries: primaries_name,
+            transfer: transfer_name,
+            hdr: (transfer_fn != TransferFunction::Sdr).then_some(self.video_hdr),
+        };
+        if self.colorspace.as_ref() != Some(&colorspace) {
+            log::info!("[Decoder] Colourspace: {:?}", colorspace);
+            self.colorspace = Some(colorspace);
+        }
 
-                        return Ok(Some(DecodeResult::Video {
-                            data: self.scaled_frame.data(0).to_vec(),
+        let direct = plane_layout(frame.format(), self.high_bit_depth)
+            .filter(|_| (target_width, target_height) == (width, height))
+            .and_then(|layout| copy_planes(frame, layout).map(|planes| (layout, planes)));
+        let (layout, planes) = match direct {
+            Some(direct) => direct,
+            None => {
+                // Downscaling keeps the source format (and range) when the GPU can take it;
+                // anything else is converted to RGBA, at 16 bits for deeper sources
+                let deep_source = self.high_bit_depth && component_depth(frame.format()) > 8;
+                let layout =
+                    plane_layout(frame.format(), self.high_bit_depth).unwrap_or(if deep_source {
+                        PlaneLayout::Rgba16
+                    } else {
+                        PlaneLayout::Rgba
+                    });
+                let output_format = match layout {
+                    PlaneLayout::Rgba => Pixel::RGBA,
+                    PlaneLayout::Rgba16 => Pixel::RGBA64LE,
+                    _ => frame.format(),
+                };
+                // Filters may hand back a different size or format than the decoder's
+                let stale = self.scaler.as_ref().map_or(true, |s| {
+                    let (input, output) = (s.input(), s.output());
+                    input.format != frame.format()
+                        || input.width != width
+                        || input.height != height
+                        || output.format != output_format
+                        || output.width != target_width
+                        || output.height != target_height
+                });
+                if stale {
+                    log::info!(
+                        "[Decoder] Scaling {:?} {}x{} -> {:?} {}x{}",
+                        frame.format(),
+                        width,
+                        height,
+                        output_format,
+                        target_width,
+                        target_height
+                    );
+                    self.scaler = Some(
+                        Context::get(
+                            frame.format(),
                             width,
                             height,
-                            stride: stride as u32,
-                            pts: pts_secs,
-                        }));
+                            output_format,
+                            target_width,
+                            target_height,
+                            Flags::BILINEAR,
+                        )
+                        .map_err(|e| {
+                            eprintln!("[Decoder] Failed to initialize scaler: {}", e);
+                            e
+                        })?,
+                    );
+                    // run() only allocates into an empty frame of the new definition
+                    self.scaled_frame = Video::empty();
+                    self.scaler_color = None;
+                }
+                if let Some(scaler) = self.scaler.as_mut() {
+                    // Only conversions to RGBA apply a matrix; YUV downscales keep it
+                    let to_rgba = matches!(layout, PlaneLayout::Rgba | PlaneLayout::Rgba16);
+                    if to_rgba && self.scaler_color != Some((matrix, range)) {
+                        set_scaler_colorspace(scaler, matrix, range);
+                        self.scaler_color = Some((matrix, range));
                     }
+                    scaler.run(frame, &mut self.scaled_frame)?;
+                }
+                match copy_planes(&self.scaled_frame, layout) {
+                    Some(planes) => (layout, planes),
+                    None => return Ok(()),
                 }
-            } else if Some(stream.index()) == self.audio_stream_index {
-                let audio_time_base = stream.time_base();
-                let _pts_secs = pts as f64 * (audio_time_base.0 as f64 / audio_time_base.1 as f64);
+            }
+        };
 
-                if let Some(ref mut ad) = self.audio_decoder {
-                    match ad.send_packet(&packet) {
-                        Ok(_) => {}
-                        Err(e) => {
-                            log::warn!(
-                                "[Decoder] Audio send_packet error: {:?} - ignoring and continuing",
-                                e
-                            );
-                        }
-                    }
-                    let mut frames_decoded = 0;
-                    while ad.receive_frame(&mut self.audio_frame).is_ok() {
-                        frames_decoded += 1;
-                        // Manual conversion now handles all audio (see below)
-
-                        // Manual conversion for I16 formats to avoid swresample issues
-                        let frame_format = self.audio_frame.format();
-                        let frame_rate = self.audio_frame.rate();
-                        let frame_channels = self.audio_frame.channels();
-
-                        // Check if we can use manual conversion (I16 Packed or F32 Planar, 1-2 channels)
-                        let use_manual =
-                            (matches!(frame_format, ffmpeg::util::format::sample::Sample::I16(_))
-                                || matches!(
-                                    frame_format,
-                                    ffmpeg::util::format::sample::Sample::F32(_)
-                                ))
-                                && frame_channels <= 2;
-
-                        if use_manual {
-                            let input_samples = self.audio_frame.samples();
-                            let output_samples = if frame_rate == 48000 {
-                                input_samples
-                            } else {
-                                ((input_samples as u64 * 48000) / frame_rate as u64) as usize
-                            };
-
-                            // Handle F32 Planar (most video files)
-                            if matches!(
-                                frame_format,
-                                ffmpeg::util::format::sample::Sample::F32(
-                                    ffmpeg::util::format::sample::Type::Planar
-                                )
-                            ) {
-                                let left_data = self.audio_frame.data(0);
-                                let left_f32: &[f32] = unsafe {
-                                    std::slice::from_raw_parts(
-                                        left_data.as_ptr() as *const f32,
-                                        input_samples,
-                                    )
-                                };
-
-                                if frame_channels == 1 {
-                                    for i in 0..output_samples {
-                                        let src_idx = if frame_rate == 48000 {
-                                            i
-                                        } else {
-                                            ((i as u64 * frame_rate as u64) / 48000) as usize
-                                        };
-                                        if src_idx >= input_samples {
-                                            break;
-                                        }
-                                        let sample = left_f32[src_idx];
-                                        self.audio_buffer.push(sample);
-                                        self.audio_buffer.push(sample);
-                                    }
-                                } else {
-                                    let right_data = self.audio_frame.data(1);
-                                    let right_f32: &[f32] = unsafe {
-                                        std::slice::from_raw_parts(
-                                            right_data.as_ptr() as *const f32,
-                                            input_samples,
-                                        )
-                                    };
-                                    for i in 0..output_samples {
-                                        let src_idx = if frame_rate == 48000 {
-                                            i
-                                        } else {
-                                            ((i as u64 * frame_rate as u64) / 48000) as usize
-                                        };
-                                        if src_idx >= input_samples {
-                                            break;
-                                        }
-                                        self.audio_buffer.push(left_f32[src_idx]);
-                                        self.audio_buffer.push(right_f32[src_idx]);
-                                    }
-                                }
-                            } else {
-                                // Handle I16 Packed (audio-only files)
-                                let input_data = self.audio_frame.data(0);
-                                let input_i16: &[i16] = unsafe {
-                                    std::slice::from_raw_parts(
-                                        input_data.as_ptr() as *const i16,
-                                        input_samples * frame_channels as usize,
-                                    )
-                                };
-
-                                for i in 0..output_samples {
-                                    let src_idx = if frame_rate == 48000 {
-                                        i
-                                    } else {
-                                        ((i as u64 * frame_rate as u64) / 48000) as usize
-                                    };
-                                    if src_idx >= input_samples {
-                                        break;
-                                    }
-
-                                    if frame_channels == 1 {
-                                        let mono = input_i16[src_idx] as f32 / 32768.0;
-                                        self.audio_buffer.push(mono);
-                                        self.audio_buffer.push(mono);
-                                    } else {
-                                        let left = input_i16[src_idx * 2] as f32 / 32768.0;
-                                        let right = input_i16[src_idx * 2 + 1] as f32 / 32768.0;
-                                        self.audio_buffer.push(left);
-                                        self.audio_buffer.push(right);
-                                    }
-                                }
-                            }
-
-                            self.audio_pts_counter += output_samples as u64;
-                        } else {
-                            // Skip exotic formats for now
-                            log::warn!(
-                                "[Decoder] Skipping unsupported format: {:?} {}Hz {}ch",
-                                frame_format,
-                                frame_rate,
-                                frame_channels
-                            );
-                            continue;
-                        }
-                    }
-                    if frames_decoded > 0 {
-                        // Use sample counter for reliable audio timing (48kHz Stereo)
-                        let calculated_pts = self.audio_pts_counter as f64 / 48000.0;
-                        return Ok(Some(DecodeResult::Audio {
-                            pts: calculated_pts,
-                        }));
-                    } else {
-                        // No frames yet, continue reading packets
+        let bit_depth = match layout {
+            PlaneLayout::Yuv420p16 | PlaneLayout::Yuv422p16 => component_depth(frame.format()),
+            // P010/P016 keep their values in the high bits, so they read as 16-bit
+            PlaneLayout::P010 | PlaneLayout::Rgba16 => 16,
+            _ => 8,
+        };
+
+        // Frames are only passed through untouched when already at the target size
+        self.pending.push_back(DecodeResult::Video {
+            frame: VideoFrame {
+                layout,
+                width: target_width,
+                height: target_height,
+                planes,
+                bit_depth,
+                // Only the RGBA layouts keep an alpha channel
+                has_alpha: matches!(layout, PlaneLayout::Rgba | PlaneLayout::Rgba16)
+                    && has_alpha(frame.format()),
+                matrix,
+                range,
+                transfer: transfer_fn,
+                wide_gamut,
+                hdr: self.video_hdr,
+            },
+            pts: pts_secs,
+            rotation: self.video_rotation,
+            pixel_aspect: self.pixel_aspect,
+        });
+
+        Ok(())
+    }
+
+    /// Receives every frame the audio decoder has ready, converts it into `audio_buffer`
+    /// and queues one `Audio` result if any samples were kept.
+    fn receive_audio_frames(&mut self) {
+        let ad = match self.audio_decoder {
+            Some(ref mut ad) => ad,
+            None => return,
+        };
+
+        let mut frames_decoded = 0;
+        while ad.receive_frame(&mut self.audio_frame).is_ok() {
+            let buffer_start = self.audio_buffer.len();
+            if let Err(e) = self
+                .audio_converter
+                .convert(&mut self.audio_frame, &mut self.audio_buffer)
+            {
+                log::warn!(
+                    "[Decoder] Audio conversion failed ({:?} {}Hz {}ch): {} - skipping frame",
+                    self.audio_frame.format(),
+                    self.audio_frame.rate(),
+                    channel_layout(unsafe { &(*self.audio_frame.as_ptr()).ch_layout }).0,
+                    e
+                );
+                self.audio_buffer.truncate(buffer_start);
+                continue;
+            }
+
+            let output_rate = self.audio_converter.output_rate() as f64;
+            // What came out starts with samples the resampler held back from earlier frames
+            let lead = self.audio_converter.output_lead_secs();
+            let mut frame_start = self
+                .audio_frame
+                .timestamp()
+                .map(|ts| self.audio_clock.to_secs(ts) - lead);
+
+            // Precise Seek: trim samples that play before the target
+            if let Some(target) = self.audio_seek_target {
+                if let Some(start) = frame_start {
+                    let skip = ((target - start) * output_rate).max(0.0) as usize
+                        * self.audio_converter.output_channels();
+                    let pushed = self.audio_buffer.len() - buffer_start;
+                    if skip >= pushed {
+                        self.audio_buffer.truncate(buffer_start);
                         continue;
                     }
+                    self.audio_buffer.drain(buffer_start..buffer_start + skip);
+                    frame_start = Some(start.max(target));
+                }
+                self.audio_seek_target = None;
+            }
+
+            if !self.audio_counter_anchored {
+                if let Some(start) = frame_start {
+                    self.audio_pts_counter = (start.max(0.0) * output_rate) as u64;
                 }
+                self.audio_counter_anchored = true;
             }
+
+            frames_decoded += 1;
+            self.audio_pts_counter += ((self.audio_buffer.len() - buffer_start)
+                / self.audio_converter.output_channels())
+                as u64;
         }
 
-        Ok(None)
+        if frames_decoded > 0 {
+            // Use sample counter for reliable audio timing
+            let calculated_pts =
+                self.audio_pts_counter as f64 / self.audio_converter.output_rate() as f64;
+            self.pending.push_back(DecodeResult::Audio {
+                pts: calculated_pts,
+            });
+        }
     }
 }
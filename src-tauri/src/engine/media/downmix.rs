@@ -0,0 +1,377 @@
+use super::super::types::DownmixMode;
+use ffmpeg_next::util::channel_layout::ChannelLayout;
+
+const MINUS_3DB: f32 = std::f32::consts::FRAC_1_SQRT_2;
+const MINUS_6DB: f32 = 0.5;
+
+/// Where a source channel sits in the sound field, as far as a stereo fold-down cares.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Role {
+    Left,
+    Right,
+    Center,
+    Lfe,
+    SurroundLeft,
+    SurroundRight,
+    SurroundCenter,
+    Other, // Height channels etc. are not folded down
+}
+
+fn role_of(channel: ChannelLayout) -> Role {
+    let is = |set: &[ChannelLayout]| set.contains(&channel);
+
+    if is(&[
+        ChannelLayout::FRONT_LEFT,
+        ChannelLayout::FRONT_LEFT_OF_CENTER,
+        ChannelLayout::WIDE_LEFT,
+        ChannelLayout::STEREO_LEFT,
+    ]) {
+        Role::Left
+    } else if is(&[
+        ChannelLayout::FRONT_RIGHT,
+        ChannelLayout::FRONT_RIGHT_OF_CENTER,
+        ChannelLayout::WIDE_RIGHT,
+        ChannelLayout::STEREO_RIGHT,
+    ]) {
+        Role::Right
+    } else if is(&[ChannelLayout::FRONT_CENTER]) {
+        Role::Center
+    } else if is(&[ChannelLayout::LOW_FREQUENCY, ChannelLayout::LOW_FREQUENCY_2]) {
+        Role::Lfe
+    } else if is(&[
+        ChannelLayout::SIDE_LEFT,
+        ChannelLayout::BACK_LEFT,
+        ChannelLayout::SURROUND_DIRECT_LEFT,
+    ]) {
+        Role::SurroundLeft
+    } else if is(&[
+        ChannelLayout::SIDE_RIGHT,
+        ChannelLayout::BACK_RIGHT,
+        ChannelLayout::SURROUND_DIRECT_RIGHT,
+    ]) {
+        Role::SurroundRight
+    } else if is(&[ChannelLayout::BACK_CENTER]) {
+        Role::SurroundCenter
+    } else {
+        Role::Other
+    }
+}
+
+/// Lists the individual channels of `layout` in ffmpeg's native (interleaving) order.
+fn channels_of(layout: ChannelLayout) -> Vec<ChannelLayout> {
+    (0..64)
+        .map(|bit| ChannelLayout::from_bits_truncate(1u64 << bit))
+        .filter(|ch| !ch.is_empty() && layout.contains(*ch))
+        .collect()
+}
+
+/// (left, right) gains for one source channel. The LFE is dropped in every mode.
+fn gains(role: Role, mode: DownmixMode, has_center: bool) -> (f32, f32) {
+    match mode {
+        DownmixMode::ItuBs775 => match role {
+            Role::Left => (1.0, 0.0),
+            Role::Right => (0.0, 1.0),
+            Role::Center => (MINUS_3DB, MINUS_3DB),
+            Role::SurroundLeft => (MINUS_3DB, 0.0),
+            Role::SurroundRight => (0.0, MINUS_3DB),
+            Role::SurroundCenter => (MINUS_6DB, MINUS_6DB),
+            Role::Lfe | Role::Other => (0.0, 0.0),
+        },
+        DownmixMode::LoRo => match role {
+            Role::Left => (1.0, 0.0),
+            Role::Right => (0.0, 1.0),
+            Role::Center => (MINUS_3DB, MINUS_3DB),
+            Role::SurroundLeft => (MINUS_6DB, 0.0),
+            Role::SurroundRight => (0.0, MINUS_6DB),
+            Role::SurroundCenter => (MINUS_6DB * MINUS_3DB, MINUS_6DB * MINUS_3DB),
+            Role::Lfe | Role::Other => (0.0, 0.0),
+        },
+        // Surrounds are summed to mono and carried out of phase (-L / +R) so a
+        // Pro Logic style decoder can steer them back to the rear.
+        DownmixMode::LtRt => match role {
+            Role::Left => (1.0, 0.0),
+            Role::Right => (0.0, 1.0),
+            Role::Center => (MINUS_3DB, MINUS_3DB),
+            Role::SurroundLeft | Role::SurroundRight => (-MINUS_6DB, MINUS_6DB),
+            Role::SurroundCenter => (-MINUS_3DB, MINUS_3DB),
+            Role::Lfe | Role::Other => (0.0, 0.0),
+        },
+        // Without a centre channel, fall back to the mono sum of the fronts.
+        DownmixMode::CenterOnly => match role {
+            Role::Center => (1.0, 1.0),
+            Role::Left | Role::Right if !has_center => (MINUS_6DB, MINUS_6DB),
+            _ => (0.0, 0.0),
+        },
+    }
+}
+
+/// Builds a `2 x channels` fold-down matrix for `layout`, normalised so that no
+/// output channel can exceed full scale.
+fn stereo_matrix(layout: ChannelLayout, mode: DownmixMode) -> [Vec<f32>; 2] {
+    let roles: Vec<Role> = channels_of(layout).into_iter().map(role_of).collect();
+
+    // Mono plays at full level on both speakers regardless of mode
+    if roles.len() == 1 {
+        return [vec![1.0], vec![1.0]];
+    }
+
+    let has_center = roles.contains(&Role::Center);
+    let (left, right): (Vec<f32>, Vec<f32>) = roles
+        .iter()
+        .map(|role| gains(*role, mode, has_center))
+        .unzip();
+
+    let peak = left
+        .iter()
+        .map(|g| g.abs())
+        .sum::<f32>()
+        .max(right.iter().map(|g| g.abs()).sum::<f32>());
+    let scale = if peak > 1.0 { 1.0 / peak } else { 1.0 };
+
+    [
+        left.iter().map(|g| g * scale).collect(),
+        right.iter().map(|g| g * scale).collect(),
+    ]
+}
+
+/// Builds an `output_channels x source channels` matrix. When every source channel
+/// exists on the output device it is routed discretely; otherwise (and always for the
+/// dialog check) the source is folded down to the device's front pair with `mode`.
+fn output_matrix(
+    source: ChannelLayout,
+    output_channels: usize,
+    mode: DownmixMode,
+) -> Vec<Vec<f32>> {
+    let source_channels = channels_of(source);
+    // cpal doesn't report speaker positions, so assume ffmpeg's default order
+    let output_layout = channels_of(ChannelLayout::default(output_channels as i32));
+    let output_known = output_layout.len() == output_channels;
+
+    let discrete = mode != DownmixMode::CenterOnly
+        && output_known
+        && source_channels.iter().all(|ch| output_layout.contains(ch));
+    if discrete {
+        return output_layout
+            .iter()
+            .map(|out_ch| {
+                source_channels
+                    .iter()
+                    .map(|src_ch| if src_ch == out_ch { 1.0 } else { 0.0 })
+                    .collect()
+            })
+            .collect();
+    }
+
+    let [left, right] = stereo_matrix(source, mode);
+    if output_channels == 1 {
+        return vec![left
+            .iter()
+            .zip(&right)
+            .map(|(l, r)| (l + r) * 0.5)
+            .collect()];
+    }
+
+    let position = |ch: ChannelLayout, fallback: usize| {
+        output_layout
+            .iter()
+            .position(|c| *c == ch)
+            .filter(|_| output_known)
+            .unwrap_or(fallback)
+    };
+    let left_index = position(ChannelLayout::FRONT_LEFT, 0);
+    let right_index = position(ChannelLayout::FRONT_RIGHT, 1);
+
+    let silent = vec![0.0; source_channels.len()];
+    (0..output_channels)
+        .map(|i| {
+            if i == left_index {
+                left.clone()
+            } else if i == right_index {
+                right.clone()
+            } else {
+                silent.clone()
+            }
+        })
+        .collect()
+}
+
+/// Maps interleaved source audio onto the output device's channels.
+pub struct Downmixer {
+    mode: DownmixMode,
+    output_channels: usize,
+    layout: Option<ChannelLayout>,
+    matrix: Vec<Vec<f32>>,
+}
+
+impl Downmixer {
+    pub fn new(mode: DownmixMode, output_channels: usize) -> Self {
+        Self {
+            mode,
+            output_channels,
+            layout: None,
+            matrix: Vec::new(),
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: DownmixMode) {
+        if self.mode != mode {
+            log::info!("[Downmix] Switching matrix to {:?}", mode);
+            self.mode = mode;
+            self.layout = None; // Rebuild on next frame
+        }
+    }
+
+    /// Mixes `input` (interleaved, channels in `layout` order) and appends interleaved
+    /// output frames to `out`.
+    pub fn process(&mut self, layout: ChannelLayout, input: &[f32], out: &mut Vec<f32>) {
+        if self.layout != Some(layout) {
+            self.matrix = output_matrix(layout, self.output_channels, self.mode);
+            self.layout = Some(layout);
+            log::info!(
+                "[Downmix] {:?} matrix {}ch -> {}ch: {:?}",
+                self.mode,
+                layout.channels(),
+                self.output_channels,
+                self.matrix
+            );
+        }
+
+        let channels = layout.channels() as usize;
+        if channels == 0 || self.matrix.is_empty() {
+            return;
+        }
+
+        out.reserve(input.len() / channels * self.matrix.len());
+        for frame in input.chunks_exact(channels) {
+            for row in &self.matrix {
+                let sample: f32 = frame.iter().zip(row).map(|(s, g)| s * g).sum();
+                out.push(sample);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SURROUND_51: ChannelLayout = ChannelLayout::_5POINT1_BACK;
+
+    fn assert_close(actual: &[f32], expected: &[f32], what: &str) {
+        assert_eq!(actual.len(), expected.len(), "{}", what);
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                (a - e).abs() < 1e-5,
+                "{}: {:?} != {:?}",
+                what,
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn folds_5_1_down_to_stereo() {
+        // Source order is FL FR FC LFE BL BR; each row is scaled by its peak sum
+        let itu = 1.0 / (1.0 + 2.0 * MINUS_3DB);
+        let loro = 1.0 / (1.0 + MINUS_3DB + MINUS_6DB);
+        let ltrt = 1.0 / (1.0 + MINUS_3DB + 2.0 * MINUS_6DB);
+        let cases = [
+            (
+                DownmixMode::ItuBs775,
+                [1.0, 0.0, MINUS_3DB, 0.0, MINUS_3DB, 0.0].map(|g| g * itu),
+                [0.0, 1.0, MINUS_3DB, 0.0, 0.0, MINUS_3DB].map(|g| g * itu),
+            ),
+            (
+                DownmixMode::LoRo,
+                [1.0, 0.0, MINUS_3DB, 0.0, MINUS_6DB, 0.0].map(|g| g * loro),
+                [0.0, 1.0, MINUS_3DB, 0.0, 0.0, MINUS_6DB].map(|g| g * loro),
+            ),
+            (
+                DownmixMode::LtRt,
+                [1.0, 0.0, MINUS_3DB, 0.0, -MINUS_6DB, -MINUS_6DB].map(|g| g * ltrt),
+                [0.0, 1.0, MINUS_3DB, 0.0, MINUS_6DB, MINUS_6DB].map(|g| g * ltrt),
+            ),
+            (
+                DownmixMode::CenterOnly,
+                [0.0, 0.0, 1.0, 0.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0, 0.0, 0.0],
+            ),
+        ];
+        for (mode, left, right) in cases {
+            let [l, r] = stereo_matrix(SURROUND_51, mode);
+            assert_close(&l, &left, &format!("{:?} left", mode));
+            assert_close(&r, &right, &format!("{:?} right", mode));
+            for row in [&l, &r] {
+                let peak: f32 = row.iter().map(|g| g.abs()).sum();
+                assert!(peak <= 1.0 + 1e-5, "{:?} peaks at {}", mode, peak);
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_mono_and_centreless_sources_audible() {
+        let cases = [
+            (
+                ChannelLayout::MONO,
+                DownmixMode::LtRt,
+                [vec![1.0], vec![1.0]],
+            ),
+            (
+                ChannelLayout::STEREO,
+                DownmixMode::CenterOnly,
+                [vec![MINUS_6DB, MINUS_6DB], vec![MINUS_6DB, MINUS_6DB]],
+            ),
+            (
+                ChannelLayout::STEREO,
+                DownmixMode::ItuBs775,
+                [vec![1.0, 0.0], vec![0.0, 1.0]],
+            ),
+        ];
+        for (layout, mode, [left, right]) in cases {
+            let [l, r] = stereo_matrix(layout, mode);
+            assert_close(&l, &left, &format!("{:?} left", mode));
+            assert_close(&r, &right, &format!("{:?} right", mode));
+        }
+    }
+
+    #[test]
+    fn routes_discretely_when_the_device_has_every_channel() {
+        let identity: Vec<Vec<f32>> = (0..6)
+            .map(|i| (0..6).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+            .collect();
+        assert_eq!(
+            output_matrix(SURROUND_51, 6, DownmixMode::ItuBs775),
+            identity
+        );
+
+        // Centre-only always folds, leaving the device's other channels silent
+        let matrix = output_matrix(SURROUND_51, 6, DownmixMode::CenterOnly);
+        assert_eq!(matrix[0], [0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+        assert_eq!(matrix[1], [0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+        assert!(matrix[2..].iter().flatten().all(|g| *g == 0.0));
+
+        // Stereo on a 5.1 device only feeds the front pair
+        let matrix = output_matrix(ChannelLayout::STEREO, 6, DownmixMode::LoRo);
+        assert_eq!(matrix.len(), 6);
+        assert_eq!(matrix[0], [1.0, 0.0]);
+        assert_eq!(matrix[1], [0.0, 1.0]);
+        assert!(matrix[2..].iter().flatten().all(|g| *g == 0.0));
+    }
+
+    #[test]
+    fn mixes_interleaved_frames() {
+        let mut downmixer = Downmixer::new(DownmixMode::CenterOnly, 2);
+        let mut out = Vec::new();
+        // Two frames: dialog only, then everything but dialog
+        let input = [0.0, 0.0, 0.5, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 1.0, 1.0, 1.0];
+        downmixer.process(SURROUND_51, &input, &mut out);
+        assert_eq!(out, [0.5, 0.5, 0.0, 0.0]);
+
+        // A new mode or layout rebuilds the matrix
+        downmixer.set_mode(DownmixMode::ItuBs775);
+        out.clear();
+        downmixer.process(ChannelLayout::MONO, &[0.25, -0.5], &mut out);
+        assert_eq!(out, [0.25, 0.25, -0.5, -0.5]);
+    }
+}
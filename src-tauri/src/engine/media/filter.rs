@@ -0,0 +1,284 @@
+use super::super::types::{DeinterlaceMode, VideoFilterFailure};
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::filter::Graph;
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::util::frame::video::Video;
+
+// Frames enter the graph stamped in microseconds on the presentation timeline
+const GRAPH_TIME_BASE: i64 = 1_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum FieldOrder {
+    TopFirst,
+    BottomFirst,
+}
+
+/// Field order from the frame flags, or `None` for a progressive frame.
+fn field_order(frame: &Video) -> Option<FieldOrder> {
+    if !frame.is_interlaced() {
+        None
+    } else if frame.is_top_first() {
+        Some(FieldOrder::TopFirst)
+    } else {
+        Some(FieldOrder::BottomFirst)
+    }
+}
+
+/// libavfilter chain for `mode`, or `None` when frames pass through untouched.
+fn deinterlace_filter(mode: DeinterlaceMode, order: Option<FieldOrder>) -> Option<String> {
+    // Forced modes fall back to top field first for frames that aren't flagged
+    let parity = match order {
+        Some(FieldOrder::BottomFirst) => "bff",
+        _ => "tff",
+    };
+    match mode {
+        DeinterlaceMode::Off => None,
+        // yadif only touches frames flagged interlaced and reads their field order itself
+        DeinterlaceMode::Auto => Some("yadif=mode=send_frame:parity=auto:deint=interlaced".into()),
+        // One line-doubled frame per field: twice the frame rate
+        DeinterlaceMode::Bob => Some(format!(
+            "setfield={},separatefields,scale=w=iw:h=ih*2:flags=bilinear",
+            parity
+        )),
+        DeinterlaceMode::Yadif => {
+            Some(format!("yadif=mode=send_frame:parity={}:deint=all", parity))
+        }
+    }
+}
+
+struct ActiveGraph {
+    graph: Graph,
+    spec: String,
+    // Input definition the graph was configured for
+    format: Pixel,
+    width: u32,
+    height: u32,
+    // Seconds per tick of the sink's timestamps
+    output_time_base: f64,
+}
+
+impl ActiveGraph {
+    fn build(spec: &str, frame: &Video) -> anyhow::Result<Self> {
+        let sar = frame.aspect_ratio();
+        let sar = if sar.0 > 0 && sar.1 > 0 {
+            sar
+        } else {
+            ffmpeg::Rational(1, 1)
+        };
+        let pix_fmt: ffmpeg::ffi::AVPixelFormat = frame.format().into();
+        let args = format!(
+            "video_size={}x{}:pix_fmt={}:time_base=1/{}:pixel_aspect={}/{}",
+            frame.width(),
+            frame.height(),
+            pix_fmt as i32,
+            GRAPH_TIME_BASE,
+            sar.0,
+            sar.1
+        );
+
+        let mut graph = Graph::new();
+        let buffer =
+            ffmpeg::filter::find("buffer").ok_or_else(|| anyhow::anyhow!("No buffer filter"))?;
+        let buffersink = ffmpeg::filter::find("buffersink")
+            .ok_or_else(|| anyhow::anyhow!("No buffersink filter"))?;
+        graph.add(&buffer, "in", &args)?;
+        graph.add(&buffersink, "out", "")?;
+        graph.output("in", 0)?.input("out", 0)?.parse(spec)?;
+        graph.validate()?;
+
+        let output_time_base = {
+            let sink = graph
+                .get("out")
+                .ok_or_else(|| anyhow::anyhow!("Filter graph has no sink"))?;
+            let tb = unsafe { ffmpeg::ffi::av_buffersink_get_time_base(sink.as_ptr()) };
+            if tb.den != 0 {
+                tb.num as f64 / tb.den as f64
+            } else {
+                1.0 / GRAPH_TIME_BASE as f64
+            }
+        };
+
+        Ok(Self {
+            graph,
+            spec: spec.to_string(),
+            format: frame.format(),
+            width: frame.width(),
+            height: frame.height(),
+            output_time_base,
+        })
+    }
+
+    fn matches(&self, spec: &str, frame: &Video) -> bool {
+        self.spec == spec
+            && self.format == frame.format()
+            && self.width == frame.width()
+            && self.height == frame.height()
+    }
+}
+
+/// Checks that `spec` parses and links as a video filter chain, using a stand-in
+/// `width`x`height` YUV 4:2:0 input.
+pub fn validate_video_filter(spec: &str, width: u32, height: u32) -> anyhow::Result<()> {
+    ffmpeg::init()?;
+    let frame = Video::new(Pixel::YUV420P, width.max(2), height.max(2));
+    ActiveGraph::build(spec, &frame)
+        .map(|_| ())
+        .map_err(|e| anyhow::anyhow!("Invalid filter graph '{}': {}", spec, e))
+}
+
+/// Runs decoded video frames through libavfilter: the deinterlacer followed by the
+/// user's filter chain. The graph is built lazily from the first frame and rebuilt when
+/// either setting or the frame definition changes.
+pub struct VideoFilter {
+    deinterlace: DeinterlaceMode,
+    user_spec: Option<String>,
+    graph: Option<ActiveGraph>,
+    // Field order of the first frame flagged interlaced; Auto mode passes frames
+    // straight through until there is one
+    field_order: Option<FieldOrder>,
+    // A chain that failed to build; not retried until the settings change
+    failed_spec: Option<String>,
+    // The latest build failure, until the caller takes it to report
+    failure: Option<VideoFilterFailure>,
+    last_pts: f64,
+}
+
+impl VideoFilter {
+    pub fn new(deinterlace: DeinterlaceMode) -> Self {
+        Self {
+            deinterlace,
+            user_spec: None,
+            graph: None,
+            field_order: None,
+            failed_spec: None,
+            failure: None,
+            last_pts: 0.0,
+        }
+    }
+
+    /// Switches the deinterlace mode; takes effect from the next decoded frame.
+    pub fn set_deinterlace_mode(&mut self, mode: DeinterlaceMode) {
+        if self.deinterlace != mode {
+            log::info!("[VideoFilter] Switching deinterlace mode to {:?}", mode);
+            self.deinterlace = mode;
+            self.graph = None;
+            self.failed_spec = None;
+        }
+    }
+
+    /// Sets the user's filter chain (e.g. `eq=gamma=1.2,hflip`), or clears it with `None`.
+    pub fn set_user_filter(&mut self, spec: Option<&str>) {
+        if self.user_spec.as_deref() != spec {
+            log::info!("[VideoFilter] User filter set to {:?}", spec);
+            self.user_spec = spec.map(str::to_string);
+            self.graph = None;
+            self.failed_spec = None;
+        }
+    }
+
+    /// The chain that last failed to build, once per failure.
+    pub fn take_failure(&mut self) -> Option<VideoFilterFailure> {
+        self.failure.take()
+    }
+
+    /// Output frames per decoded frame: 2 while bob deinterlacing, otherwise 1.
+    pub fn rate_factor(&self) -> u32 {
+        if self.deinterlace == DeinterlaceMode::Bob {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Drops the graph and any frames it is holding (e.g. after a seek or stream switch).
+    pub fn reset(&mut self) {
+        self.graph = None;
+        self.field_order = None;
+    }
+
+    /// Feeds a decoded frame stamped at `pts_secs` into the graph. Returns `false` when
+    /// no filtering applies and the frame should be used as-is.
+    pub fn push(&mut self, frame: &mut Video, pts_secs: f64) -> bool {
+        if self.field_order.is_none() {
+            self.field_order = field_order(frame);
+        }
+        let deinterlace = if self.deinterlace == DeinterlaceMode::Auto && self.field_order.is_none()
+        {
+            None
+        } else {
+            deinterlace_filter(self.deinterlace, self.field_order)
+        };
+
+        let spec = match (deinterlace, self.user_spec.as_deref()) {
+            (Some(deinterlace), Some(user)) => format!("{},{}", deinterlace, user),
+            (Some(deinterlace), None) => deinterlace,
+            (None, Some(user)) => user.to_string(),
+            (None, None) => return false,
+        };
+        if self.failed_spec.as_deref() == Some(spec.as_str()) {
+            return false;
+        }
+
+        if !self.graph.as_ref().is_some_and(|g| g.matches(&spec, frame)) {
+            match ActiveGraph::build(&spec, frame) {
+                Ok(graph) => {
+                    log::info!(
+                        "[VideoFilter] {}x{} {:?}, field order {:?}: {}",
+                        frame.width(),
+                        frame.height(),
+                        frame.format(),
+                        self.field_order,
+                        spec
+                    );
+                    self.graph = Some(graph);
+                }
+                Err(e) => {
+                    log::error!("[VideoFilter] Failed to build '{}': {}", spec, e);
+                    self.graph = None;
+                    self.failure = Some(VideoFilterFailure {
+                        filter: spec.clone(),
+                        error: e.to_string(),
+                    });
+                    self.failed_spec = Some(spec);
+                    return false;
+                }
+            }
+        }
+
+        let graph = match self.graph.as_mut() {
+            Some(g) => g,
+            None => return false,
+        };
+        frame.set_pts(Some((pts_secs * GRAPH_TIME_BASE as f64).round() as i64));
+        let added = match graph.graph.get("in") {
+            Some(mut ctx) => ctx.source().add(frame),
+            None => Err(ffmpeg::Error::Bug),
+        };
+        if let Err(e) = added {
+            log::warn!("[VideoFilter] Failed to queue frame: {}", e);
+        }
+        true
+    }
+
+    /// Signals end of stream so the graph releases the frames it is holding back.
+    pub fn flush(&mut self) {
+        if let Some(graph) = self.graph.as_mut() {
+            if let Some(mut ctx) = graph.graph.get("in") {
+                if let Err(e) = ctx.source().flush() {
+                    log::warn!("[VideoFilter] Flush failed: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Fetches the next filtered frame into `frame` and returns its timeline seconds.
+    pub fn pull(&mut self, frame: &mut Video) -> Option<f64> {
+        let graph = self.graph.as_mut()?;
+        let mut sink = graph.graph.get("out")?;
+        sink.sink().frame(frame).ok()?;
+        if let Some(pts) = frame.pts() {
+            self.last_pts = pts as f64 * graph.output_time_base;
+        }
+        Some(self.last_pts)
+    }
+}
@@ -0,0 +1,73 @@
+use super::super::types::{HdrMetadata, TransferFunction};
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::codec::packet::side_data::Type as StreamSideData;
+use ffmpeg_next::color;
+use ffmpeg_next::util::frame::side_data::Type as FrameSideData;
+use ffmpeg_next::util::frame::video::Video;
+
+fn rational_nits(r: ffmpeg::ffi::AVRational) -> Option<f32> {
+    (r.num > 0 && r.den > 0).then(|| r.num as f32 / r.den as f32)
+}
+
+fn light_level(value: u32) -> Option<f32> {
+    (value > 0).then_some(value as f32)
+}
+
+/// Reads an `AVMasteringDisplayMetadata` payload into `hdr`.
+fn read_mastering_display(data: &[u8], hdr: &mut HdrMetadata) {
+    if data.len() < std::mem::size_of::<ffmpeg::ffi::AVMasteringDisplayMetadata>() {
+        return;
+    }
+    let metadata = unsafe { &*(data.as_ptr() as *const ffmpeg::ffi::AVMasteringDisplayMetadata) };
+    if metadata.has_luminance != 0 {
+        hdr.mastering_max_nits = rational_nits(metadata.max_luminance);
+        hdr.mastering_min_nits = rational_nits(metadata.min_luminance);
+    }
+}
+
+/// Reads an `AVContentLightMetadata` payload into `hdr`.
+fn read_content_light(data: &[u8], hdr: &mut HdrMetadata) {
+    if data.len() < std::mem::size_of::<ffmpeg::ffi::AVContentLightMetadata>() {
+        return;
+    }
+    let metadata = unsafe { &*(data.as_ptr() as *const ffmpeg::ffi::AVContentLightMetadata) };
+    hdr.max_cll = light_level(metadata.MaxCLL);
+    hdr.max_fall = light_level(metadata.MaxFALL);
+}
+
+/// HDR metadata from the container (e.g. the MP4 `mdcv`/`clli` boxes or Matroska colour
+/// elements).
+pub fn stream_hdr_metadata(stream: &ffmpeg::format::stream::Stream) -> HdrMetadata {
+    let mut hdr = HdrMetadata::default();
+    for side_data in stream.side_data() {
+        match side_data.kind() {
+            StreamSideData::MasteringDisplayMetadata => {
+                read_mastering_display(side_data.data(), &mut hdr)
+            }
+            StreamSideData::ContentLightLevel => read_content_light(side_data.data(), &mut hdr),
+            _ => {}
+        }
+    }
+    hdr
+}
+
+/// HDR metadata the decoder attached to `frame` (e.g. from HEVC SEI messages).
+pub fn frame_hdr_metadata(frame: &Video) -> HdrMetadata {
+    let mut hdr = HdrMetadata::default();
+    if let Some(side_data) = frame.side_data(FrameSideData::MasteringDisplayMetadata) {
+        read_mastering_display(side_data.data(), &mut hdr);
+    }
+    if let Some(side_data) = frame.side_data(FrameSideData::ContentLightLevel) {
+        read_content_light(side_data.data(), &mut hdr);
+    }
+    hdr
+}
+
+/// Transfer function the renderer needs to decode `frame` with.
+pub fn transfer_function(frame: &Video) -> TransferFunction {
+    match frame.color_transfer_characteristic() {
+        color::TransferCharacteristic::SMPTE2084 => TransferFunction::Pq,
+        color::TransferCharacteristic::ARIB_STD_B67 => TransferFunction::Hlg,
+        _ => TransferFunction::Sdr,
+    }
+}
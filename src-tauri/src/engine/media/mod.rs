@@ -1,3 +1,31 @@
+pub mod animation;
+pub mod archive;
+pub mod avio;
 pub mod decoder;
+pub mod downmix;
+pub mod filter;
+pub mod hdr;
+pub mod probe;
+pub mod resampler;
+pub mod sequence;
+pub mod sidecar;
+pub mod streams;
+pub mod subtitles;
+pub mod timeline;
+pub mod webp;
 
+pub use animation::*;
+pub use archive::*;
+pub use avio::*;
 pub use decoder::*;
+pub use downmix::*;
+pub use filter::*;
+pub use hdr::*;
+pub use probe::*;
+pub use resampler::*;
+pub use sequence::*;
+pub use sidecar::*;
+pub use streams::*;
+pub use subtitles::*;
+pub use timeline::*;
+pub use webp::*;
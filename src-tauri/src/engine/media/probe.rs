@@ -0,0 +1,157 @@
+use super::super::types::{
+    AttachedPicture, AudioProbe, MediaProbe, ProbeStream, StreamKind, VideoProbe,
+};
+use super::animation::probe_animation;
+use super::archive::ArchiveEntry;
+use super::avio::AvioInput;
+use super::sequence::ImageSequence;
+use super::streams::{stream_channels, stream_kind};
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::format::stream::Disposition;
+use std::collections::BTreeMap;
+use std::ffi::{c_char, CStr};
+use std::path::Path;
+
+/// Converts a name returned by a libav* lookup; null and "unknown" become `None`.
+pub(super) unsafe fn lookup_name(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    let name = CStr::from_ptr(ptr).to_string_lossy();
+    if name.is_empty() || name == "unknown" {
+        None
+    } else {
+        Some(name.into_owned())
+    }
+}
+
+fn rational_to_f64(r: ffmpeg::Rational) -> Option<f64> {
+    (r.0 > 0 && r.1 > 0).then(|| r.0 as f64 / r.1 as f64)
+}
+
+fn tags(metadata: ffmpeg::DictionaryRef) -> BTreeMap<String, String> {
+    metadata
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+/// Reads container and stream details from the file headers without decoding any
+/// frames. Covers what the UI used to get from the ffprobe sidecar. One frame of an image
+/// sequence is reported with the sequence it belongs to, at `sequence_frame_rate`.
+pub fn probe_media(path: &Path, sequence_frame_rate: f64) -> anyhow::Result<MediaProbe> {
+    ffmpeg::init()?;
+    let archive_entry = ArchiveEntry::parse(path);
+    // Archive entries play as themselves, as in the playback engine
+    let sequence = match archive_entry {
+        Some(_) => None,
+        None => ImageSequence::detect(path, sequence_frame_rate),
+    };
+    // Declared before the input so that it is freed after it
+    let mut _avio = None;
+    let input_ctx = match &archive_entry {
+        Some(entry) => {
+            let (input_ctx, io) = AvioInput::open(entry.open()?, &entry.name)?;
+            _avio = Some(io);
+            input_ctx
+        }
+        None => ffmpeg::format::input(&path).map_err(|e| {
+            log::error!("[Probe] Failed to open input for {:?}: {}", path, e);
+            anyhow::anyhow!("FFmpeg input error: {}", e)
+        })?,
+    };
+
+    let mut streams = Vec::new();
+    let mut attached_pictures = Vec::new();
+    for stream in input_ctx.streams() {
+        let kind = stream_kind(stream.parameters().medium());
+        let id = stream.parameters().id();
+        let codec = id.name().to_string();
+
+        // An unopened codec context exposes the parameters as typed enums
+        let context = ffmpeg::codec::context::Context::from_parameters(stream.parameters())?;
+        let ctx = unsafe { &*context.as_ptr() };
+
+        let duration = rational_to_f64(stream.time_base())
+            .filter(|_| stream.duration() > 0)
+            .map(|tb| stream.duration() as f64 * tb);
+
+        let video = (kind == StreamKind::Video).then(|| unsafe {
+            VideoProbe {
+                width: ctx.width.max(0) as u32,
+                height: ctx.height.max(0) as u32,
+                pixel_format: lookup_name(ffmpeg::ffi::av_get_pix_fmt_name(ctx.pix_fmt)),
+                frame_rate: rational_to_f64(stream.rate())
+                    .or_else(|| rational_to_f64(stream.avg_frame_rate())),
+                color_primaries: lookup_name(ffmpeg::ffi::av_color_primaries_name(
+                    ctx.color_primaries,
+                )),
+                color_transfer: lookup_name(ffmpeg::ffi::av_color_transfer_name(ctx.color_trc)),
+                color_matrix: lookup_name(ffmpeg::ffi::av_color_space_name(ctx.colorspace)),
+                color_range: lookup_name(ffmpeg::ffi::av_color_range_name(ctx.color_range)),
+            }
+        });
+
+        let audio = (kind == StreamKind::Audio).then(|| {
+            let (channels, channel_layout) = stream_channels(&stream.parameters());
+            AudioProbe {
+                sample_rate: ctx.sample_rate.max(0) as u32,
+                channels,
+                channel_layout,
+                sample_format: unsafe {
+                    lookup_name(ffmpeg::ffi::av_get_sample_fmt_name(ctx.sample_fmt))
+                },
+            }
+        });
+
+        if stream.disposition().contains(Disposition::ATTACHED_PIC) {
+            attached_pictures.push(AttachedPicture {
+                stream_index: stream.index(),
+                codec: codec.clone(),
+                width: ctx.width.max(0) as u32,
+                height: ctx.height.max(0) as u32,
+                size: unsafe { (*stream.as_ptr()).attached_pic.size.max(0) as usize },
+            });
+        }
+
+        streams.push(ProbeStream {
+            index: stream.index(),
+            kind,
+            codec,
+            codec_long_name: ffmpeg::decoder::find(id).map(|c| c.description().to_string()),
+            profile: unsafe {
+                lookup_name(ffmpeg::ffi::avcodec_profile_name(ctx.codec_id, ctx.profile))
+            },
+            // FF_LEVEL_UNKNOWN is negative
+            level: (ctx.level > 0).then_some(ctx.level),
+            bit_rate: (ctx.bit_rate > 0).then_some(ctx.bit_rate),
+            duration,
+            tags: tags(stream.metadata()),
+            video,
+            audio,
+        });
+    }
+
+    let format = input_ctx.format();
+    Ok(MediaProbe {
+        container: format.name().to_string(),
+        container_long_name: format.description().to_string(),
+        duration: if let Some(sequence) = &sequence {
+            sequence.duration()
+        } else if input_ctx.duration() > 0 {
+            input_ctx.duration() as f64 / 1_000_000.0
+        } else {
+            0.0
+        },
+        size: match archive_entry {
+            Some(_) => unsafe { ffmpeg::ffi::avio_size((*input_ctx.as_ptr()).pb).max(0) as u64 },
+            None => std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+        },
+        bit_rate: input_ctx.bit_rate().max(0),
+        tags: tags(input_ctx.metadata()),
+        streams,
+        attached_pictures,
+        animation: probe_animation(path),
+        sequence: sequence.map(|s| s.info()),
+    })
+}
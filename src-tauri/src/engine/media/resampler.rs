@@ -0,0 +1,263 @@
+use super::super::types::{AudioOutputFormat, DownmixMode, ResampleQuality};
+use super::downmix::Downmixer;
+use super::streams::channel_layout;
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::software::resampling::Context;
+use ffmpeg_next::util::channel_layout::ChannelLayout;
+use ffmpeg_next::util::format::sample::{Sample, Type as SampleType};
+use ffmpeg_next::util::frame::audio::Audio;
+
+// Headroom for samples the resampler holds back from the previous frame
+const OUTPUT_SLACK_SAMPLES: usize = 256;
+
+/// Layout of `frame`, read from its `ch_layout` as the UI reports it. The frame is given
+/// that layout in native order (WAVs often leave it unspecified) so swr takes it as the
+/// layout the context was built for.
+fn frame_layout(frame: &mut Audio) -> ChannelLayout {
+    let (_, layout) = channel_layout(unsafe { &(*frame.as_ptr()).ch_layout });
+    unsafe {
+        let ch_layout = &mut (*frame.as_mut_ptr()).ch_layout;
+        if ch_layout.order != ffmpeg::ffi::AVChannelOrder::AV_CHANNEL_ORDER_NATIVE {
+            ffmpeg::ffi::av_channel_layout_uninit(ch_layout);
+            ffmpeg::ffi::av_channel_layout_from_mask(ch_layout, layout.bits());
+        }
+    }
+    // The deprecated field swr falls back on
+    frame.set_channel_layout(layout);
+    layout
+}
+
+/// swresample filter options for each quality preset. All presets use its windowed-sinc
+/// polyphase filter; they trade filter length and phase resolution against CPU.
+fn quality_options(quality: ResampleQuality) -> &'static [(&'static str, &'static str)] {
+    match quality {
+        ResampleQuality::Fast => &[
+            ("filter_size", "8"),
+            ("phase_shift", "6"),
+            ("linear_interp", "0"),
+            ("cutoff", "0.90"),
+        ],
+        ResampleQuality::Medium => &[
+            ("filter_size", "32"),
+            ("phase_shift", "10"),
+            ("linear_interp", "1"),
+            ("cutoff", "0.97"),
+        ],
+        ResampleQuality::Best => &[
+            ("filter_size", "64"),
+            ("phase_shift", "14"),
+            ("linear_interp", "1"),
+            ("cutoff", "0.985"),
+            ("exact_rational", "1"),
+        ],
+    }
+}
+
+/// Applies `quality` to an existing resampler and re-initialises it. Re-initialising
+/// also clears the filter history and any buffered samples.
+fn configure(context: &mut Context, quality: Option<ResampleQuality>) -> anyhow::Result<()> {
+    unsafe {
+        let ptr = context.as_mut_ptr();
+        if let Some(quality) = quality {
+            for (key, value) in quality_options(quality) {
+                let key = std::ffi::CString::new(*key)?;
+                let value = std::ffi::CString::new(*value)?;
+                let ret = ffmpeg::ffi::av_opt_set(
+                    ptr as *mut std::ffi::c_void,
+                    key.as_ptr(),
+                    value.as_ptr(),
+                    0,
+                );
+                if ret < 0 {
+                    return Err(ffmpeg::Error::from(ret).into());
+                }
+            }
+        }
+        let ret = ffmpeg::ffi::swr_init(ptr);
+        if ret < 0 {
+            return Err(ffmpeg::Error::from(ret).into());
+        }
+    }
+    Ok(())
+}
+
+/// Converts decoded audio frames of any sample format, channel layout and rate
+/// (S16/S32/F32/DBL, packed or planar) into interleaved f32 at the output device's
+/// rate and channel count. ffmpeg's software resampler handles format and rate; the
+/// source layout is kept so the `Downmixer` can route or fold down the channels.
+pub struct AudioConverter {
+    context: Option<Context>,
+    output: Audio,
+    downmix: Downmixer,
+    output_rate: u32,
+    output_channels: usize,
+    quality: ResampleQuality,
+    // The live context was built with a different preset and must be reconfigured
+    quality_changed: bool,
+    // Output samples the resampler was still holding from earlier input when the last
+    // frame went in; that frame's output starts with them
+    held_samples: usize,
+}
+
+impl AudioConverter {
+    pub fn new(
+        output_format: &AudioOutputFormat,
+        downmix_mode: DownmixMode,
+        quality: ResampleQuality,
+    ) -> Self {
+        let output_channels = output_format.channels.max(1) as usize;
+        Self {
+            context: None,
+            output: Audio::empty(),
+            downmix: Downmixer::new(downmix_mode, output_channels),
+            output_rate: output_format.sample_rate,
+            output_channels,
+            quality,
+            quality_changed: false,
+            held_samples: 0,
+        }
+    }
+
+    pub fn output_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    pub fn output_channels(&self) -> usize {
+        self.output_channels
+    }
+
+    /// How far, in seconds, the output of the last `convert` starts before the frame that
+    /// went in: the samples swr held back from earlier frames (longer filters hold more).
+    pub fn output_lead_secs(&self) -> f64 {
+        self.held_samples as f64 / self.output_rate.max(1) as f64
+    }
+
+    pub fn set_downmix_mode(&mut self, mode: DownmixMode) {
+        self.downmix.set_mode(mode);
+    }
+
+    pub fn set_quality(&mut self, quality: ResampleQuality) {
+        if self.quality != quality {
+            log::info!("[Resampler] Switching quality to {:?}", quality);
+            self.quality = quality;
+            self.quality_changed = true;
+        }
+    }
+
+    /// Clears the filter history and any samples the resampler is still holding (e.g.
+    /// after a seek) while keeping the configured context and quality preset.
+    pub fn reset(&mut self) {
+        if let Some(context) = self.context.as_mut() {
+            if let Err(e) = configure(context, None) {
+                log::warn!("[Resampler] Reset failed, rebuilding on next frame: {}", e);
+                self.context = None;
+            }
+        }
+    }
+
+    /// Converts `frame` and appends the interleaved result to `out`.
+    /// Returns the number of samples produced per channel.
+    pub fn convert(&mut self, frame: &mut Audio, out: &mut Vec<f32>) -> anyhow::Result<usize> {
+        let format = frame.format();
+        let layout = frame_layout(frame);
+        let rate = frame.rate();
+
+        // (Re)build the resampler whenever the source definition changes mid-stream
+        let needs_context = self.context.as_ref().map_or(true, |c| {
+            let input = c.input();
+            input.format != format || input.channel_layout != layout || input.rate != rate
+        });
+        if needs_context {
+            log::info!(
+                "[Resampler] Configuring {:?} {}Hz {}ch -> F32 {}Hz {}ch",
+                format,
+                rate,
+                layout.channels(),
+                self.output_rate,
+                self.output_channels
+            );
+            let mut context = Context::get(
+                format,
+                layout,
+                rate,
+                Sample::F32(SampleType::Packed),
+                layout,
+                self.output_rate,
+            )?;
+            configure(&mut context, Some(self.quality))?;
+            self.context = Some(context);
+            self.quality_changed = false;
+        } else if self.quality_changed {
+            // Filter state is lost on reconfigure; a one-off seam beats rebuilding every frame
+            if let Some(context) = self.context.as_mut() {
+                configure(context, Some(self.quality))?;
+            }
+            self.quality_changed = false;
+        }
+
+        let context = match self.context.as_mut() {
+            Some(c) => c,
+            None => return Ok(0),
+        };
+
+        // swr_convert_frame treats the allocated sample count as capacity and shrinks it
+        // to what was written, so allocate a fresh frame sized for the rate ratio each time.
+        let capacity = (frame.samples() as u64 * self.output_rate as u64 / rate.max(1) as u64)
+            as usize
+            + OUTPUT_SLACK_SAMPLES;
+        self.output = Audio::new(Sample::F32(SampleType::Packed), capacity, layout);
+        // The safe wrapper's delay() rounds to whole seconds first, so ask swr directly
+        let held =
+            unsafe { ffmpeg::ffi::swr_get_delay(context.as_mut_ptr(), self.output_rate as i64) };
+        self.held_samples = held.max(0) as usize;
+        context.run(frame, &mut self.output)?;
+
+        let samples = self.output.samples();
+        let data = self.output.data(0);
+        let interleaved: &[f32] = unsafe {
+            std::slice::from_raw_parts(
+                data.as_ptr() as *const f32,
+                samples * layout.channels() as usize,
+            )
+        };
+        self.downmix.process(layout, interleaved, out);
+
+        Ok(samples)
+    }
+
+    /// Drains the samples still held inside the resampler's filter at end of stream and
+    /// appends them to `out`. Returns the number of samples produced per channel.
+    pub fn flush(&mut self, out: &mut Vec<f32>) -> anyhow::Result<usize> {
+        let context = match self.context.as_mut() {
+            Some(c) => c,
+            None => return Ok(0),
+        };
+        let layout = context.output().channel_layout;
+
+        let mut total = 0;
+        loop {
+            self.output = Audio::new(
+                Sample::F32(SampleType::Packed),
+                OUTPUT_SLACK_SAMPLES * 4,
+                layout,
+            );
+            context.flush(&mut self.output)?;
+
+            let samples = self.output.samples();
+            if samples == 0 {
+                break;
+            }
+            let data = self.output.data(0);
+            let interleaved: &[f32] = unsafe {
+                std::slice::from_raw_parts(
+                    data.as_ptr() as *const f32,
+                    samples * layout.channels() as usize,
+                )
+            };
+            self.downmix.process(layout, interleaved, out);
+            total += samples;
+        }
+
+        Ok(total)
+    }
+}
@@ -0,0 +1,333 @@
+use super::super::types::SequenceInfo;
+use ffmpeg_next as ffmpeg;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+// Still formats that renders are delivered as
+const SEQUENCE_EXTENSIONS: &[&str] = &[
+    "png", "exr", "dpx", "tif", "tiff", "tga", "jpg", "jpeg", "bmp", "webp",
+];
+
+fn is_sequence_extension(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| SEQUENCE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Splits a pattern like `shot_%04d.exr` or `shot_####.exr` into prefix, padding width and
+/// suffix. `%d` is unpadded (width 0).
+fn parse_pattern(name: &str) -> Option<(String, usize, String)> {
+    if let Some(start) = name.find('%') {
+        let rest = &name[start + 1..];
+        let digits = rest.find('d')?;
+        let spec = &rest[..digits];
+        let width = if spec.is_empty() {
+            0
+        } else if spec.starts_with('0') && spec.bytes().all(|b| b.is_ascii_digit()) {
+            spec.parse().ok()?
+        } else {
+            return None;
+        };
+        return Some((
+            name[..start].to_string(),
+            width,
+            rest[digits + 1..].to_string(),
+        ));
+    }
+    let start = name.find('#')?;
+    let width = name[start..].bytes().take_while(|&b| b == b'#').count();
+    Some((
+        name[..start].to_string(),
+        width,
+        name[start + width..].to_string(),
+    ))
+}
+
+/// Splits a frame's file name, e.g. `shot_0012.png`, around the digits ending its stem.
+fn parse_frame_name(name: &str) -> Option<(String, usize, String)> {
+    let stem_end = name.rfind('.')?;
+    let stem = &name[..stem_end];
+    let digits = stem.bytes().rev().take_while(u8::is_ascii_digit).count();
+    if digits == 0 {
+        return None;
+    }
+    Some((
+        stem[..stem.len() - digits].to_string(),
+        digits,
+        name[stem_end..].to_string(),
+    ))
+}
+
+/// Numbered frames on disk: `prefix`, the frame number zero-padded to `width` digits,
+/// then `suffix`. Played through FFmpeg's concat demuxer with one entry per frame.
+#[derive(Clone, Debug)]
+pub struct ImageSequence {
+    dir: PathBuf,
+    prefix: String,
+    width: usize,
+    suffix: String,
+    // Frame numbers present on disk, ascending
+    frames: Vec<u64>,
+    frame_rate: f64,
+}
+
+impl ImageSequence {
+    /// Recognises `path` as a sequence: either a pattern (`shot_%04d.png`, `shot_####.png`)
+    /// or one frame of a run of at least two numbered images in the same folder.
+    pub fn detect(path: &Path, frame_rate: f64) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        if !is_sequence_extension(name) {
+            return None;
+        }
+        let (parsed, picked_frame) = match parse_pattern(name) {
+            Some(parsed) => (parsed, false),
+            None => (parse_frame_name(name)?, true),
+        };
+        let (prefix, width, suffix) = parsed;
+        // The frame list lives in the temp folder, so its paths must be absolute
+        let parent = path.parent().filter(|p| !p.as_os_str().is_empty());
+        let dir = std::path::absolute(parent.unwrap_or(Path::new("."))).ok()?;
+
+        let mut sequence = Self {
+            dir,
+            prefix,
+            width,
+            suffix,
+            frames: Vec::new(),
+            frame_rate,
+        };
+        sequence.frames = std::fs::read_dir(&sequence.dir)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| sequence.frame_number(entry.file_name().to_str()?))
+            .collect();
+        sequence.frames.sort_unstable();
+
+        // A lone numbered image is just a still
+        let min_frames = if picked_frame { 2 } else { 1 };
+        if sequence.frames.len() < min_frames {
+            return None;
+        }
+        log::info!(
+            "[Sequence] {} frames of {} at {} fps",
+            sequence.frames.len(),
+            sequence.pattern(),
+            frame_rate
+        );
+        Some(sequence)
+    }
+
+    /// The frame number `name` carries, if it belongs to this sequence.
+    fn frame_number(&self, name: &str) -> Option<u64> {
+        let digits = name
+            .strip_prefix(self.prefix.as_str())?
+            .strip_suffix(self.suffix.as_str())?;
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let number: u64 = digits.parse().ok()?;
+        // Same padding only: shot_01.png is not a frame of shot_%04d.png
+        (format!("{:0width$}", number, width = self.width) == digits).then_some(number)
+    }
+
+    fn frame_path(&self, number: u64) -> PathBuf {
+        self.dir.join(format!(
+            "{}{:0width$}{}",
+            self.prefix,
+            number,
+            self.suffix,
+            width = self.width
+        ))
+    }
+
+    /// printf-style pattern covering every frame.
+    pub fn pattern(&self) -> String {
+        let number = if self.width == 0 {
+            "%d".to_string()
+        } else {
+            format!("%0{}d", self.width)
+        };
+        self.dir
+            .join(format!("{}{}{}", self.prefix, number, self.suffix))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    pub fn info(&self) -> SequenceInfo {
+        let mut missing = Vec::new();
+        for pair in self.frames.windows(2) {
+            if pair[1] > pair[0] + 1 {
+                missing.push((pair[0] + 1, pair[1] - 1));
+            }
+        }
+        SequenceInfo {
+            pattern: self.pattern(),
+            first_frame: self.frames[0],
+            last_frame: self.frames[self.frames.len() - 1],
+            frame_rate: self.frame_rate,
+            frame_count: self.frames.len(),
+            missing,
+        }
+    }
+
+    /// Timeline position of frame `number`, in whole microseconds so entries don't drift.
+    fn frame_start_us(&self, number: u64) -> u64 {
+        ((number - self.frames[0]) as f64 * 1_000_000.0 / self.frame_rate).round() as u64
+    }
+
+    /// Length of the clip, from the first frame to the end of the last.
+    pub fn duration(&self) -> f64 {
+        self.frame_start_us(self.frames[self.frames.len() - 1] + 1) as f64 / 1_000_000.0
+    }
+
+    /// The file on screen at `time` seconds; through a gap that is the frame before it.
+    pub fn frame_at(&self, time: f64) -> PathBuf {
+        let offset = (time.max(0.0) * self.frame_rate + 1e-6).floor() as u64;
+        let target = self.frames[0] + offset;
+        let shown = self.frames.partition_point(|&n| n <= target).max(1) - 1;
+        self.frame_path(self.frames[shown])
+    }
+
+    /// ffconcat list with one entry per frame on disk, each lasting until the next.
+    fn concat_list(&self) -> String {
+        let mut list = String::from("ffconcat version 1.0\n");
+        for (i, &number) in self.frames.iter().enumerate() {
+            let next = self.frames.get(i + 1).copied().unwrap_or(number + 1);
+            let duration_us = self.frame_start_us(next) - self.frame_start_us(number);
+            // Single quotes keep backslashes literal; a quote itself is closed around
+            let path = self
+                .frame_path(number)
+                .to_string_lossy()
+                .replace('\'', "'\\''");
+            list.push_str(&format!(
+                "file '{}'\nduration {}.{:06}\n",
+                path,
+                duration_us / 1_000_000,
+                duration_us % 1_000_000
+            ));
+        }
+        list
+    }
+
+    /// Writes the frame list to the temp folder and opens it as the input.
+    pub fn open(&self) -> anyhow::Result<ffmpeg::format::context::Input> {
+        let mut hasher = DefaultHasher::new();
+        self.pattern().hash(&mut hasher);
+        self.frame_rate.to_bits().hash(&mut hasher);
+        let list_path = std::env::temp_dir().join(format!(
+            "kenichi-sequence-{:016x}.ffconcat",
+            hasher.finish()
+        ));
+        std::fs::write(&list_path, self.concat_list())?;
+
+        let mut options = ffmpeg::Dictionary::new();
+        // The list names frames by absolute path, which concat refuses by default
+        options.set("safe", "0");
+        ffmpeg::format::input_with_dictionary(&list_path, options).map_err(|e| {
+            log::error!("[Sequence] Failed to open {}: {}", self.pattern(), e);
+            anyhow::anyhow!("FFmpeg input error: {}", e)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(prefix: &str, width: usize, suffix: &str) -> Option<(String, usize, String)> {
+        Some((prefix.to_string(), width, suffix.to_string()))
+    }
+
+    #[test]
+    fn parses_patterns() {
+        let cases = [
+            ("shot_%04d.exr", split("shot_", 4, ".exr")),
+            ("%d.png", split("", 0, ".png")),
+            ("shot_####.png", split("shot_", 4, ".png")),
+            ("a#b.tga", split("a", 1, "b.tga")),
+            ("shot_%4d.exr", None),
+            ("shot_%s.exr", None),
+            ("shot_0001.png", None),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(parse_pattern(name), expected, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn parses_frame_names() {
+        let cases = [
+            ("shot_0012.png", split("shot_", 4, ".png")),
+            ("7.exr", split("", 1, ".exr")),
+            ("v2_final.0100.dpx", split("v2_final.", 4, ".dpx")),
+            ("shot.png", None),
+            ("0012", None),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(parse_frame_name(name), expected, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn detects_sequences_with_gaps() {
+        let dir =
+            std::env::temp_dir().join(format!("kenichi-sequence-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // Frames 1-3 and 6 of shot_####; shot_05 has the wrong padding
+        for name in [
+            "shot_0001.png",
+            "shot_0002.png",
+            "shot_0003.png",
+            "shot_0006.png",
+            "shot_05.png",
+            "lone_0001.png",
+            "notes.txt",
+        ] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+
+        let cases = [
+            ("shot_%04d.png", Some(4)),
+            ("shot_####.png", Some(4)),
+            ("shot_0002.png", Some(4)),
+            ("lone_0001.png", None),
+            ("lone_%04d.png", Some(1)),
+            ("notes.txt", None),
+        ];
+        for (name, frames) in cases {
+            let sequence = ImageSequence::detect(&dir.join(name), 24.0);
+            assert_eq!(sequence.map(|s| s.frames.len()), frames, "{:?}", name);
+        }
+
+        let sequence = ImageSequence::detect(&dir.join("shot_0001.png"), 25.0).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let info = sequence.info();
+        assert_eq!((info.first_frame, info.last_frame), (1, 6));
+        assert_eq!(info.frame_count, 4);
+        assert_eq!(info.missing, [(4, 5)]);
+        assert!(info.pattern.ends_with("shot_%04d.png"), "{}", info.pattern);
+        assert!((sequence.duration() - 0.24).abs() < 1e-9);
+
+        // Through the gap the last frame before it stays up
+        let cases = [
+            (-1.0, "shot_0001.png"),
+            (0.04, "shot_0002.png"),
+            (0.15, "shot_0003.png"),
+            (0.2, "shot_0006.png"),
+            (10.0, "shot_0006.png"),
+        ];
+        for (time, name) in cases {
+            assert_eq!(sequence.frame_at(time), dir.join(name), "at {}", time);
+        }
+
+        let list = sequence.concat_list();
+        let durations: Vec<&str> = list
+            .lines()
+            .filter_map(|line| line.strip_prefix("duration "))
+            .collect();
+        assert_eq!(durations, ["0.040000", "0.040000", "0.120000", "0.040000"]);
+    }
+}
@@ -0,0 +1,487 @@
+use super::super::types::{SubtitleContent, SubtitleCue, SubtitleFileError};
+use super::subtitles::strip_ass_tags;
+use std::path::Path;
+
+/// Cues loaded from an external .srt, .vtt or .ass/.ssa file, sorted by start time.
+pub struct SubtitleFile {
+    cues: Vec<SubtitleCue>,
+}
+
+impl SubtitleFile {
+    pub fn load(path: &Path) -> Result<Self, SubtitleFileError> {
+        let bytes = std::fs::read(path).map_err(|e| SubtitleFileError::Io {
+            message: e.to_string(),
+        })?;
+        let text = decode_text(&bytes);
+
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase())
+            .unwrap_or_default();
+        let mut cues = match extension.as_str() {
+            "srt" => parse_srt(&text)?,
+            "vtt" => parse_vtt(&text)?,
+            "ass" | "ssa" => parse_ass(&text)?,
+            _ => return Err(SubtitleFileError::UnsupportedFormat { extension }),
+        };
+        if cues.is_empty() {
+            return Err(SubtitleFileError::Empty);
+        }
+
+        cues.sort_by(|a, b| a.start.total_cmp(&b.start));
+        log::info!("[Subtitles] Loaded {} cues from {:?}", cues.len(), path);
+        Ok(Self { cues })
+    }
+
+    pub fn cue_count(&self) -> usize {
+        self.cues.len()
+    }
+
+    /// The most recently started cue showing at `time`, identified by its position.
+    pub fn active_at(&self, time: f64) -> Option<(u64, &SubtitleContent)> {
+        let started = self.cues.partition_point(|cue| cue.start <= time);
+        self.cues[..started]
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, cue)| cue.end.is_some_and(|end| end > time))
+            .map(|(i, cue)| (i as u64, &cue.content))
+    }
+}
+
+/// UTF-8 (with or without BOM) and UTF-16 with BOM are decoded as such; anything else is
+/// taken as Latin-1, the usual encoding of older SRT files.
+fn decode_text(bytes: &[u8]) -> String {
+    let utf16 = |be: bool| {
+        let units: Vec<u16> = bytes[2..]
+            .chunks_exact(2)
+            .map(|c| {
+                if be {
+                    u16::from_be_bytes([c[0], c[1]])
+                } else {
+                    u16::from_le_bytes([c[0], c[1]])
+                }
+            })
+            .collect();
+        String::from_utf16_lossy(&units)
+    };
+
+    match bytes {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        [0xFF, 0xFE, ..] => utf16(false),
+        [0xFE, 0xFF, ..] => utf16(true),
+        _ => match std::str::from_utf8(bytes) {
+            Ok(text) => text.to_string(),
+            Err(_) => {
+                log::warn!("[Subtitles] File is not UTF-8, reading it as Latin-1");
+                bytes.iter().map(|&b| b as char).collect()
+            }
+        },
+    }
+}
+
+fn parse_error(index: usize, message: impl Into<String>) -> SubtitleFileError {
+    SubtitleFileError::Parse {
+        line: index + 1,
+        message: message.into(),
+    }
+}
+
+/// Parses "HH:MM:SS,mmm", "HH:MM:SS.mmm", "MM:SS.mmm" and ASS "H:MM:SS.cc" into seconds.
+fn parse_timestamp(value: &str) -> Option<f64> {
+    let parts: Vec<&str> = value.trim().split(':').collect();
+    if parts.len() < 2 || parts.len() > 3 {
+        return None;
+    }
+    let seconds: f64 = parts[parts.len() - 1].replace(',', ".").parse().ok()?;
+    let mut total = seconds;
+    for (i, part) in parts[..parts.len() - 1].iter().rev().enumerate() {
+        let value: u64 = part.parse().ok()?;
+        total += value as f64 * 60f64.powi(i as i32 + 1);
+    }
+    Some(total)
+}
+
+/// Parses "start --> end", ignoring WebVTT cue settings after the end time.
+fn parse_timing(line: &str) -> Option<(f64, f64)> {
+    let (start, rest) = line.split_once("-->")?;
+    let end = rest.split_whitespace().next()?;
+    Some((parse_timestamp(start)?, parse_timestamp(end)?))
+}
+
+/// Drops HTML-style tags (<i>, <font ...>, <c.yellow>, <v Speaker>) and decodes the
+/// common character references.
+fn strip_markup(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if in_tag => {}
+            _ => out.push(c),
+        }
+    }
+    out.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+/// Reads cue blocks (optional identifier, timing line, text lines, blank line) shared by
+/// SRT and WebVTT. `lines` starts after any file header.
+fn parse_cue_blocks<'a>(
+    lines: impl Iterator<Item = (usize, &'a str)>,
+    clean: impl Fn(&str) -> String,
+    skip_block: impl Fn(&str) -> bool,
+) -> Result<Vec<SubtitleCue>, SubtitleFileError> {
+    let mut lines = lines.peekable();
+    let mut cues = Vec::new();
+
+    loop {
+        // Blank lines between blocks
+        while lines.next_if(|(_, l)| l.trim().is_empty()).is_some() {}
+        let (mut index, mut line) = match lines.next() {
+            Some(entry) => entry,
+            None => break,
+        };
+
+        if skip_block(line) {
+            while lines.next_if(|(_, l)| !l.trim().is_empty()).is_some() {}
+            continue;
+        }
+
+        // Cue identifier / SRT counter
+        if !line.contains("-->") {
+            match lines.next() {
+                Some(entry) => (index, line) = entry,
+                None => return Err(parse_error(index, "cue has no timing line")),
+            }
+        }
+
+        let (start, end) = parse_timing(line)
+            .ok_or_else(|| parse_error(index, format!("invalid timing line '{}'", line.trim())))?;
+        if end < start {
+            return Err(parse_error(index, "cue ends before it starts"));
+        }
+
+        let mut text = Vec::new();
+        while let Some((_, l)) = lines.next_if(|(_, l)| !l.trim().is_empty()) {
+            text.push(clean(l.trim_end()));
+        }
+
+        cues.push(SubtitleCue {
+            start,
+            end: Some(end),
+            content: SubtitleContent::Text(text.join("\n")),
+        });
+    }
+
+    Ok(cues)
+}
+
+fn parse_srt(text: &str) -> Result<Vec<SubtitleCue>, SubtitleFileError> {
+    // Some SRT authoring tools embed ASS positioning tags like {\an8}
+    parse_cue_blocks(
+        text.lines().enumerate(),
+        |l| strip_ass_tags(&strip_markup(l)),
+        |_| false,
+    )
+}
+
+fn parse_vtt(text: &str) -> Result<Vec<SubtitleCue>, SubtitleFileError> {
+    let mut lines = text.lines().enumerate();
+    match lines.next() {
+        Some((_, header)) if header.starts_with("WEBVTT") => {}
+        _ => return Err(parse_error(0, "missing WEBVTT header")),
+    }
+    // Header block (metadata lines) runs until the first blank line
+    let lines = lines.skip_while(|(_, l)| !l.trim().is_empty());
+
+    parse_cue_blocks(lines, strip_markup, |l| {
+        l.starts_with("NOTE") || l.starts_with("STYLE") || l.starts_with("REGION")
+    })
+}
+
+fn parse_ass(text: &str) -> Result<Vec<SubtitleCue>, SubtitleFileError> {
+    let mut cues = Vec::new();
+    let mut in_events = false;
+    let mut format: Option<Vec<String>> = None;
+
+    for (index, raw) in text.lines().enumerate() {
+        let line = raw.trim();
+        if line.starts_with('[') {
+            in_events = line.eq_ignore_ascii_case("[events]");
+            continue;
+        }
+        if !in_events {
+            continue;
+        }
+
+        if let Some(rest) = line.strip_prefix("Format:") {
+            format = Some(
+                rest.split(',')
+                    .map(|f| f.trim().to_ascii_lowercase())
+                    .collect(),
+            );
+            continue;
+        }
+
+        let rest = match line.strip_prefix("Dialogue:") {
+            Some(rest) => rest,
+            None => continue,
+        };
+        let fields = format
+            .as_ref()
+            .ok_or_else(|| parse_error(index, "Dialogue line before the Format line"))?;
+        // The last field (Text) may itself contain commas
+        let values: Vec<&str> = rest.splitn(fields.len(), ',').collect();
+        if values.len() < fields.len() {
+            return Err(parse_error(
+                index,
+                format!("expected {} fields, found {}", fields.len(), values.len()),
+            ));
+        }
+        let field = |name: &str| {
+            fields
+                .iter()
+                .position(|f| f == name)
+                .map(|i| values[i].trim())
+        };
+
+        let start = field("start")
+            .and_then(parse_timestamp)
+            .ok_or_else(|| parse_error(index, "invalid Start time"))?;
+        let end = field("end")
+            .and_then(parse_timestamp)
+            .ok_or_else(|| parse_error(index, "invalid End time"))?;
+
+        cues.push(SubtitleCue {
+            start,
+            end: Some(end),
+            content: SubtitleContent::Text(strip_ass_tags(field("text").unwrap_or(""))),
+        });
+    }
+
+    Ok(cues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (start, end, text) of each cue.
+    fn summary(cues: &[SubtitleCue]) -> Vec<(f64, f64, &str)> {
+        cues.iter()
+            .map(|cue| match &cue.content {
+                SubtitleContent::Text(text) => (cue.start, cue.end.unwrap(), text.as_str()),
+                SubtitleContent::Bitmap { .. } => panic!("text file gave a bitmap cue"),
+            })
+            .collect()
+    }
+
+    fn error_line(result: Result<Vec<SubtitleCue>, SubtitleFileError>) -> usize {
+        match result {
+            Err(SubtitleFileError::Parse { line, .. }) => line,
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_timestamps() {
+        let cases = [
+            ("00:01:02,500", Some(62.5)),
+            ("01:00:00.250", Some(3600.25)),
+            ("02:03.5", Some(123.5)),
+            ("0:00:05.12", Some(5.12)),
+            (" 00:00:01,000 ", Some(1.0)),
+            ("12", None),
+            ("1:02:03:04", None),
+            ("aa:00:01", None),
+            ("00:00:xx", None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_timestamp(value), expected, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn parses_timing_lines() {
+        let cases = [
+            ("00:00:01,000 --> 00:00:02,500", Some((1.0, 2.5))),
+            (
+                "00:01.000 --> 00:02.000 align:start line:0",
+                Some((1.0, 2.0)),
+            ),
+            ("00:00:01,000 -> 00:00:02,000", None),
+            ("00:00:01,000 -->", None),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(parse_timing(line), expected, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn strips_markup() {
+        let cases = [
+            ("<i>Hello</i>", "Hello"),
+            ("<font color=\"red\">a &amp; b</font>", "a & b"),
+            ("<v Roger><c.yellow>Hi</c> &lt;3", "Hi <3"),
+            ("1 &gt; 0&nbsp;!", "1 > 0 !"),
+            ("say &quot;cheese&quot;", "say \"cheese\""),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(strip_markup(text), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn decodes_text_encodings() {
+        let utf16 = |be: bool| {
+            let mut bytes = if be {
+                vec![0xFE, 0xFF]
+            } else {
+                vec![0xFF, 0xFE]
+            };
+            for unit in "Æon".encode_utf16() {
+                bytes.extend(if be {
+                    unit.to_be_bytes()
+                } else {
+                    unit.to_le_bytes()
+                });
+            }
+            bytes
+        };
+        let cases = [
+            (b"\xEF\xBB\xBFplain".to_vec(), "plain"),
+            ("caf\u{e9}".as_bytes().to_vec(), "café"),
+            (b"caf\xE9".to_vec(), "café"),
+            (utf16(false), "Æon"),
+            (utf16(true), "Æon"),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(decode_text(&bytes), expected, "{:?}", bytes);
+        }
+    }
+
+    #[test]
+    fn parses_srt() {
+        let text = "1\r\n00:00:01,000 --> 00:00:02,500\r\n{\\an8}<i>Top</i> line\r\nsecond\r\n\r\n\
+                    2\n00:00:03,000 --> 00:00:04,000\nNext\n";
+        let cues = parse_srt(text).unwrap();
+        assert_eq!(
+            summary(&cues),
+            [(1.0, 2.5, "Top line\nsecond"), (3.0, 4.0, "Next")]
+        );
+
+        let cases = [
+            // Counter with nothing after it
+            ("1\n", 1),
+            ("1\n00:00:01,000 to 00:00:02,000\ntext\n", 2),
+            ("\n\n7\n00:00:05,000 --> 00:00:04,000\ntext\n", 4),
+        ];
+        for (text, line) in cases {
+            assert_eq!(error_line(parse_srt(text)), line, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn parses_vtt() {
+        let text = "WEBVTT - a title\nKind: captions\n\n\
+                    NOTE a comment\nspanning lines\n\n\
+                    STYLE\n::cue { color: yellow }\n\n\
+                    intro\n00:01.000 --> 00:02.000 position:10%\n<v Roger>Hello</v>\n\n\
+                    00:00:03.000 --> 00:00:04.500\n<c.loud>Bye</c> &amp; out\n";
+        let cues = parse_vtt(text).unwrap();
+        assert_eq!(
+            summary(&cues),
+            [(1.0, 2.0, "Hello"), (3.0, 4.5, "Bye & out")]
+        );
+
+        assert_eq!(error_line(parse_vtt("00:01.000 --> 00:02.000\nHi\n")), 1);
+    }
+
+    #[test]
+    fn parses_ass() {
+        let text = "[Script Info]\nTitle: test\n\n\
+                    [V4+ Styles]\nFormat: Name, Fontname\nStyle: Default,Arial\n\n\
+                    [Events]\n\
+                    Format: Layer, Start, End, Style, Text\n\
+                    Comment: 0,0:00:00.00,0:00:01.00,Default,ignored\n\
+                    Dialogue: 0,0:00:01.50,0:00:03.00,Default,{\\i1}Hello, world\\Nagain\n\
+                    Dialogue: 0,0:01:00.00,0:01:02.25,Default,Later\n";
+        let cues = parse_ass(text).unwrap();
+        assert_eq!(
+            summary(&cues),
+            [(1.5, 3.0, "Hello, world\nagain"), (60.0, 62.25, "Later")]
+        );
+
+        let cases = [
+            (
+                "[Events]\nDialogue: 0,0:00:01.00,0:00:02.00,Default,Hi\n",
+                2,
+            ),
+            (
+                "[Events]\nFormat: Layer, Start, End, Text\nDialogue: 0,0:00:01.00\n",
+                3,
+            ),
+            (
+                "[Events]\nFormat: Start, End, Text\nDialogue: soon,0:00:02.00,Hi\n",
+                3,
+            ),
+        ];
+        for (text, line) in cases {
+            assert_eq!(error_line(parse_ass(text)), line, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn finds_the_cue_on_screen() {
+        let path = std::env::temp_dir().join(format!("kenichi-sidecar-{}.srt", std::process::id()));
+        // Out of order, and overlapping
+        std::fs::write(
+            &path,
+            "2\n00:00:02,000 --> 00:00:03,000\nB\n\n1\n00:00:01,000 --> 00:00:05,000\nA\n",
+        )
+        .unwrap();
+        let file = SubtitleFile::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(file.cue_count(), 2);
+
+        let text_at = |time| match file.active_at(time) {
+            Some((id, SubtitleContent::Text(text))) => Some((id, text.as_str())),
+            _ => None,
+        };
+        let cases = [
+            (0.5, None),
+            (1.0, Some((0, "A"))),
+            (2.5, Some((1, "B"))),
+            (4.0, Some((0, "A"))),
+            (5.0, None),
+        ];
+        for (time, expected) in cases {
+            assert_eq!(text_at(time), expected, "at {}", time);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_and_empty_files() {
+        let dir = std::env::temp_dir();
+        let unknown = dir.join(format!("kenichi-sidecar-{}.sub", std::process::id()));
+        std::fs::write(&unknown, "{1}{2}Hi").unwrap();
+        let result = SubtitleFile::load(&unknown);
+        std::fs::remove_file(&unknown).unwrap();
+        assert!(matches!(
+            result,
+            Err(SubtitleFileError::UnsupportedFormat { extension }) if extension == "sub"
+        ));
+
+        let empty = dir.join(format!("kenichi-sidecar-{}-empty.vtt", std::process::id()));
+        std::fs::write(&empty, "WEBVTT\n\n").unwrap();
+        let result = SubtitleFile::load(&empty);
+        std::fs::remove_file(&empty).unwrap();
+        assert!(matches!(result, Err(SubtitleFileError::Empty)));
+    }
+}
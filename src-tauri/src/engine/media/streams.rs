@@ -0,0 +1,125 @@
+use super::super::types::{StreamInfo, StreamKind};
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::format::stream::Disposition;
+use ffmpeg_next::media::Type;
+use ffmpeg_next::util::channel_layout::ChannelLayout;
+
+/// Human readable name for common layouts, falling back to the channel count.
+fn layout_name(layout: ChannelLayout) -> String {
+    let known = [
+        (ChannelLayout::MONO, "mono"),
+        (ChannelLayout::STEREO, "stereo"),
+        (ChannelLayout::_2POINT1, "2.1"),
+        (ChannelLayout::SURROUND, "3.0"),
+        (ChannelLayout::QUAD, "quad"),
+        (ChannelLayout::_5POINT0, "5.0(side)"),
+        (ChannelLayout::_5POINT1, "5.1(side)"),
+        (ChannelLayout::_5POINT0_BACK, "5.0"),
+        (ChannelLayout::_5POINT1_BACK, "5.1"),
+        (ChannelLayout::_7POINT1, "7.1"),
+    ];
+    known
+        .iter()
+        .find(|(l, _)| *l == layout)
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| format!("{} channels", layout.channels()))
+}
+
+/// Channel count and layout of an `AVChannelLayout`. Only native-order layouts carry a
+/// mask; other orders get the default layout for their channel count.
+pub fn channel_layout(ch_layout: &ffmpeg::ffi::AVChannelLayout) -> (u16, ChannelLayout) {
+    let channels = ch_layout.nb_channels.max(0);
+    let mask = if ch_layout.order == ffmpeg::ffi::AVChannelOrder::AV_CHANNEL_ORDER_NATIVE {
+        unsafe { ch_layout.u.mask }
+    } else {
+        0
+    };
+    let layout = if mask != 0 {
+        ChannelLayout::from_bits_truncate(mask)
+    } else {
+        ChannelLayout::default(channels)
+    };
+    (channels as u16, layout)
+}
+
+/// Channel count and layout name of an audio stream, as the UI shows them wherever the
+/// stream is described.
+pub fn stream_channels(parameters: &ffmpeg::codec::Parameters) -> (u16, String) {
+    let (channels, layout) = channel_layout(unsafe { &(*parameters.as_ptr()).ch_layout });
+    (channels, layout_name(layout))
+}
+
+pub fn stream_kind(medium: Type) -> StreamKind {
+    match medium {
+        Type::Video => StreamKind::Video,
+        Type::Audio => StreamKind::Audio,
+        Type::Subtitle => StreamKind::Subtitle,
+        Type::Data => StreamKind::Data,
+        Type::Attachment => StreamKind::Attachment,
+        Type::Unknown => StreamKind::Unknown,
+    }
+}
+
+/// Describes every stream in `input_ctx`; the `active_*` indices mark the streams
+/// currently being decoded.
+pub fn describe_streams(
+    input_ctx: &ffmpeg::format::context::Input,
+    active_video: Option<usize>,
+    active_audio: Option<usize>,
+    active_subtitle: Option<usize>,
+) -> Vec<StreamInfo> {
+    input_ctx
+        .streams()
+        .map(|stream| {
+            let index = stream.index();
+            let parameters = stream.parameters();
+            let kind = stream_kind(parameters.medium());
+
+            // Codec parameters are read directly; the safe wrapper only exposes them
+            // through an opened decoder.
+            let (sample_rate, width, height) = unsafe {
+                let par = parameters.as_ptr();
+                ((*par).sample_rate, (*par).width, (*par).height)
+            };
+
+            let metadata = stream.metadata();
+            let language = metadata
+                .get("language")
+                .filter(|l| !l.is_empty() && *l != "und")
+                .map(str::to_string);
+            let title = metadata.get("title").map(str::to_string);
+
+            let (channels, channel_layout, sample_rate) = if kind == StreamKind::Audio {
+                let (channels, layout) = stream_channels(&parameters);
+                (
+                    Some(channels),
+                    Some(layout),
+                    Some(sample_rate.max(0) as u32),
+                )
+            } else {
+                (None, None, None)
+            };
+
+            let (width, height) = if kind == StreamKind::Video {
+                (Some(width.max(0) as u32), Some(height.max(0) as u32))
+            } else {
+                (None, None)
+            };
+
+            StreamInfo {
+                index,
+                kind,
+                codec: parameters.id().name().to_string(),
+                language,
+                title,
+                channels,
+                channel_layout,
+                sample_rate,
+                width,
+                height,
+                is_default: stream.disposition().contains(Disposition::DEFAULT),
+                active: [active_video, active_audio, active_subtitle].contains(&Some(index)),
+            }
+        })
+        .collect()
+}
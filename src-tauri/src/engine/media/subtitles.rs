@@ -0,0 +1,230 @@
+use super::super::types::{SubtitleBitmap, SubtitleContent, SubtitleCue};
+use super::timeline::StreamClock;
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::codec::subtitle::{Rect, Subtitle};
+
+/// Extracts the displayable text from an ASS event as produced by libavcodec's text
+/// decoders (SRT, mov_text, WebVTT and ASS all decode to ASS events). Override tags are
+/// dropped and ASS line breaks become '\n'.
+pub fn ass_to_text(event: &str) -> String {
+    // libavcodec emits "ReadOrder,Layer,Style,Name,MarginL,MarginR,MarginV,Effect,Text";
+    // full "Dialogue:" lines carry Start/End instead of ReadOrder
+    let (body, fields) = match event.strip_prefix("Dialogue:") {
+        Some(rest) => (rest, 9),
+        None => (event, 8),
+    };
+    strip_ass_tags(body.splitn(fields + 1, ',').nth(fields).unwrap_or(""))
+}
+
+/// Drops ASS override blocks (`{\an8}`, `{\i1}`...) and turns `\N`/`\n` into line
+/// breaks and `\h` into a space.
+pub fn strip_ass_tags(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut in_tag = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => in_tag = true,
+            '}' if in_tag => in_tag = false,
+            _ if in_tag => {}
+            '\\' => match chars.peek() {
+                Some('N') | Some('n') => {
+                    chars.next();
+                    out.push('\n');
+                }
+                Some('h') => {
+                    chars.next();
+                    out.push(' ');
+                }
+                _ => out.push(c),
+            },
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Expands a paletted (PAL8) subtitle rect into RGBA.
+unsafe fn bitmap_to_rgba(rect: *const ffmpeg::ffi::AVSubtitleRect) -> Option<SubtitleBitmap> {
+    let r = &*rect;
+    if r.w <= 0 || r.h <= 0 || r.data[0].is_null() || r.data[1].is_null() {
+        return None;
+    }
+    let (width, height) = (r.w as usize, r.h as usize);
+    // Palette entries are native-endian 0xAARRGGBB
+    let palette = std::slice::from_raw_parts(r.data[1] as *const u32, r.nb_colors.max(0) as usize);
+
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        let row = r.data[0].add(y * r.linesize[0] as usize);
+        for x in 0..width {
+            let argb = palette.get(*row.add(x) as usize).copied().unwrap_or(0);
+            rgba.extend_from_slice(&[
+                (argb >> 16) as u8,
+                (argb >> 8) as u8,
+                argb as u8,
+                (argb >> 24) as u8,
+            ]);
+        }
+    }
+
+    Some(SubtitleBitmap {
+        x: r.x.max(0) as u32,
+        y: r.y.max(0) as u32,
+        width: width as u32,
+        height: height as u32,
+        rgba,
+    })
+}
+
+/// The embedded subtitle stream currently being decoded.
+pub struct SubtitleTrack {
+    pub index: usize,
+    decoder: ffmpeg::decoder::Subtitle,
+    clock: StreamClock,
+}
+
+impl SubtitleTrack {
+    pub fn open(
+        stream: &ffmpeg::format::stream::Stream,
+        clock: StreamClock,
+    ) -> anyhow::Result<Self> {
+        let mut context = ffmpeg::codec::context::Context::from_parameters(stream.parameters())?;
+        // Text decoders time their events from this; without it every end time is 0
+        unsafe {
+            (*context.as_mut_ptr()).pkt_timebase = stream.time_base().into();
+        }
+        let decoder = context.decoder().subtitle().map_err(|e| {
+            eprintln!("[Subtitles] Failed to open subtitle decoder: {}", e);
+            e
+        })?;
+        log::info!(
+            "[Subtitles] Decoding stream {} ({})",
+            stream.index(),
+            stream.parameters().id().name()
+        );
+        Ok(Self {
+            index: stream.index(),
+            decoder,
+            clock,
+        })
+    }
+
+    /// Drops decoder state after a seek to `target` seconds.
+    pub fn flush(&mut self, target: f64) {
+        self.decoder.flush();
+        self.clock.reset(target);
+    }
+
+    /// Decodes one subtitle packet. `fallback_canvas` (the video size) positions bitmap
+    /// rects when the stream doesn't declare its own canvas.
+    pub fn decode(
+        &mut self,
+        packet: &ffmpeg::Packet,
+        fallback_canvas: (u32, u32),
+    ) -> Option<SubtitleCue> {
+        let mut subtitle = Subtitle::new();
+        match self.decoder.decode(packet, &mut subtitle) {
+            Ok(true) => {}
+            Ok(false) => return None,
+            Err(e) => {
+                log::warn!("[Subtitles] Decode error: {:?} - skipping packet", e);
+                return None;
+            }
+        }
+
+        let base = match packet.pts().or(packet.dts()) {
+            Some(ts) => self.clock.to_secs(ts),
+            None => {
+                log::warn!("[Subtitles] Packet without timestamp - skipping");
+                return None;
+            }
+        };
+        let start = base + subtitle.start() as f64 / 1000.0;
+        let end = match subtitle.end() {
+            // Not set by the decoder: the packet may still carry the duration
+            0 | u32::MAX if packet.duration() > 0 => {
+                Some(base + self.clock.duration_secs(packet.duration()))
+            }
+            // Unknown duration: the next event ends this one
+            0 | u32::MAX => None,
+            ms => Some(base + ms as f64 / 1000.0),
+        };
+
+        let mut lines = Vec::new();
+        let mut bitmaps = Vec::new();
+        for rect in subtitle.rects() {
+            match &rect {
+                Rect::Text(text) => lines.push(text.get().to_string()),
+                Rect::Ass(ass) => lines.push(ass_to_text(ass.get())),
+                Rect::Bitmap(_) => {
+                    if let Some(bitmap) = unsafe { bitmap_to_rgba(rect.as_ptr()) } {
+                        bitmaps.push(bitmap);
+                    }
+                }
+                Rect::None(_) => {}
+            }
+        }
+
+        let content = if !bitmaps.is_empty() {
+            let (mut canvas_width, mut canvas_height) = unsafe {
+                let ctx = self.decoder.as_ptr();
+                ((*ctx).width.max(0) as u32, (*ctx).height.max(0) as u32)
+            };
+            if canvas_width == 0 || canvas_height == 0 {
+                (canvas_width, canvas_height) = fallback_canvas;
+            }
+            SubtitleContent::Bitmap {
+                canvas_width,
+                canvas_height,
+                rects: bitmaps,
+            }
+        } else {
+            SubtitleContent::Text(lines.join("\n"))
+        };
+
+        Some(SubtitleCue {
+            start,
+            end,
+            content,
+        })
+    }
+}
+
+/// Cues received so far for the active track, ordered by arrival.
+#[derive(Default)]
+pub struct SubtitleTimeline {
+    cues: Vec<(u64, SubtitleCue)>,
+    next_id: u64,
+}
+
+impl SubtitleTimeline {
+    pub fn clear(&mut self) {
+        self.cues.clear();
+    }
+
+    pub fn push(&mut self, cue: SubtitleCue) {
+        // Open-ended cues (and clear events) are closed by whatever comes next
+        for (_, open) in self.cues.iter_mut() {
+            if open.end.is_none() && open.start < cue.start {
+                open.end = Some(cue.start);
+            }
+        }
+        if !cue.content.is_empty() {
+            self.cues.push((self.next_id, cue));
+            self.next_id += 1;
+        }
+    }
+
+    /// The most recently started cue showing at `time`, with an id that changes whenever
+    /// a different cue takes over. Overlapping cues are not stacked.
+    pub fn active_at(&mut self, time: f64) -> Option<(u64, &SubtitleContent)> {
+        self.cues
+            .retain(|(_, cue)| cue.end.map_or(true, |end| end > time));
+        self.cues
+            .iter()
+            .filter(|(_, cue)| cue.start <= time)
+            .max_by(|(_, a), (_, b)| a.start.total_cmp(&b.start))
+            .map(|(id, cue)| (*id, &cue.content))
+    }
+}
@@ -0,0 +1,357 @@
+use ffmpeg_next as ffmpeg;
+use std::sync::{Arc, Mutex};
+
+// Jumps larger than these (in presentation order) are treated as timestamp discontinuities
+const FORWARD_DISCONTINUITY_SECS: f64 = 10.0;
+const BACKWARD_DISCONTINUITY_SECS: f64 = 0.5;
+// A break another stream already recorded within this much raw time is the same break
+const SEGMENT_MATCH_SECS: f64 = 2.0;
+
+/// A stretch of the timeline over which raw timestamps map with one correction, in force
+/// from `start` (timeline seconds) until the next segment.
+#[derive(Clone, Copy, Debug)]
+struct TimelineSegment {
+    start: f64,
+    // Unwrapped raw time the segment starts at, in seconds
+    raw_start: f64,
+    // Added by bridged discontinuities
+    offset: f64,
+    // Added by PTS wraparound
+    wrap_secs: f64,
+}
+
+/// Discontinuities and wraps found so far in one input, shared by all its stream clocks so
+/// they bridge each break the same way and seeks can map timeline positions to raw time.
+#[derive(Clone, Default)]
+pub struct TimelineMap {
+    // In timeline order
+    segments: Arc<Mutex<Vec<TimelineSegment>>>,
+}
+
+impl TimelineMap {
+    /// Raw container time, in seconds, of timeline position `secs`.
+    pub fn raw_secs(&self, secs: f64, origin_secs: f64) -> f64 {
+        let segments = self.segments.lock().unwrap();
+        match segments.iter().rev().find(|s| s.start <= secs) {
+            Some(segment) => secs + origin_secs - segment.offset - segment.wrap_secs,
+            None => secs + origin_secs,
+        }
+    }
+}
+
+/// Maps raw stream timestamps onto the presentation timeline, where the file's first
+/// presentable frame is 0.0. Handles PTS wraparound (e.g. the 33-bit MPEG-TS clock) and,
+/// for formats that allow them, bridges timestamp discontinuities so time keeps moving
+/// forward by one frame step instead of jumping.
+pub struct StreamClock {
+    time_base: f64,
+    origin_secs: f64,
+    wrap_period: Option<i64>,
+    allow_discontinuities: bool,
+    timeline: TimelineMap,
+    // Start of the segment this clock is in, if past the first break
+    segment: Option<f64>,
+    // Timeline position sought to; the first timestamp after it picks the segment
+    seek_target: Option<f64>,
+    // Continuity state, restored from the timeline on seek
+    wrap_offset: i64,
+    discontinuity_offset: f64,
+    last_raw: Option<i64>,
+    last_secs: Option<f64>,
+    last_step: f64,
+}
+
+impl StreamClock {
+    /// `origin_secs` is the container start time; `pts_wrap_bits` comes from the stream.
+    pub fn new(
+        time_base: ffmpeg::Rational,
+        origin_secs: f64,
+        pts_wrap_bits: i32,
+        allow_discontinuities: bool,
+        timeline: TimelineMap,
+    ) -> Self {
+        let time_base = if time_base.1 != 0 {
+            time_base.0 as f64 / time_base.1 as f64
+        } else {
+            0.0
+        };
+        Self {
+            time_base,
+            origin_secs,
+            wrap_period: if pts_wrap_bits > 0 && pts_wrap_bits < 63 {
+                Some(1i64 << pts_wrap_bits)
+            } else {
+                None
+            },
+            allow_discontinuities,
+            timeline,
+            segment: None,
+            seek_target: None,
+            wrap_offset: 0,
+            discontinuity_offset: 0.0,
+            last_raw: None,
+            last_secs: None,
+            last_step: 0.0,
+        }
+    }
+
+    /// Forgets continuity state after a seek to timeline position `target`. The next
+    /// timestamp takes the correction of the segment it falls in.
+    pub fn reset(&mut self, target: f64) {
+        self.seek_target = Some(target);
+        self.segment = None;
+        self.wrap_offset = 0;
+        self.discontinuity_offset = 0.0;
+        self.last_raw = None;
+        self.last_secs = None;
+        self.last_step = 0.0;
+    }
+
+    /// Timeline position of `ts` under the current correction, without tracking it.
+    pub fn peek_secs(&self, ts: i64) -> f64 {
+        (ts + self.wrap_offset) as f64 * self.time_base - self.origin_secs
+            + self.discontinuity_offset
+    }
+
+    /// Length in seconds of a raw duration (stream time base).
+    pub fn duration_secs(&self, duration: i64) -> f64 {
+        duration as f64 * self.time_base
+    }
+
+    fn wrap_ticks(&self, wrap_secs: f64) -> i64 {
+        if self.time_base > 0.0 {
+            (wrap_secs / self.time_base).round() as i64
+        } else {
+            0
+        }
+    }
+
+    fn adopt(&mut self, segment: &TimelineSegment) {
+        self.segment = Some(segment.start);
+        self.discontinuity_offset = segment.offset;
+        self.wrap_offset = self.wrap_ticks(segment.wrap_secs);
+    }
+
+    /// Picks the segment for the first timestamp after a seek: the last one starting at
+    /// or before the target whose raw time this timestamp has reached. A keyframe just
+    /// ahead of a break lands in the segment before it.
+    fn restore_after_seek(&mut self, target: f64, ts: i64) {
+        let segments = self.timeline.segments.lock().unwrap().clone();
+        let found = segments.iter().rev().find(|s| {
+            let raw_secs = (ts + self.wrap_ticks(s.wrap_secs)) as f64 * self.time_base;
+            s.start <= target && raw_secs >= s.raw_start - SEGMENT_MATCH_SECS
+        });
+        if let Some(segment) = found {
+            self.adopt(segment);
+        }
+    }
+
+    /// Enters the segment starting at `raw_secs`: the one another stream already recorded
+    /// for this break, or a new one with this clock's own correction.
+    fn enter_segment(&mut self, raw_secs: f64, start: f64) {
+        let mut segments = self.timeline.segments.lock().unwrap();
+        let current = self.segment.unwrap_or(f64::NEG_INFINITY);
+        let recorded = segments
+            .iter()
+            .find(|s| s.start > current && (s.raw_start - raw_secs).abs() < SEGMENT_MATCH_SECS)
+            .copied();
+        match recorded {
+            Some(segment) => {
+                drop(segments);
+                self.adopt(&segment);
+            }
+            None => {
+                let segment = TimelineSegment {
+                    start,
+                    raw_start: raw_secs,
+                    offset: self.discontinuity_offset,
+                    wrap_secs: self.wrap_offset as f64 * self.time_base,
+                };
+                let at = segments.partition_point(|s| s.start <= start);
+                segments.insert(at, segment);
+                self.segment = Some(start);
+            }
+        }
+    }
+
+    /// Converts a raw timestamp (stream time base) into timeline seconds.
+    pub fn to_secs(&mut self, ts: i64) -> f64 {
+        if let Some(target) = self.seek_target.take() {
+            self.restore_after_seek(target, ts);
+        }
+
+        let mut raw = ts + self.wrap_offset;
+        let mut wrapped = false;
+        if let (Some(period), Some(last)) = (self.wrap_period, self.last_raw) {
+            if last - raw > period / 2 {
+                // Counter wrapped past its maximum
+                self.wrap_offset += period;
+                raw += period;
+                wrapped = true;
+            } else if raw - last > period / 2 {
+                // A late frame from just before the wrap
+                raw -= period;
+            }
+        }
+
+        let raw_secs = raw as f64 * self.time_base;
+        let mut secs = raw_secs - self.origin_secs + self.discontinuity_offset;
+        if wrapped {
+            self.enter_segment(raw_secs, secs);
+            raw = ts + self.wrap_offset;
+            secs = raw as f64 * self.time_base - self.origin_secs + self.discontinuity_offset;
+        }
+        self.last_raw = Some(raw);
+
+        let secs = match self.last_secs {
+            Some(prev) if self.allow_discontinuities => {
+                let delta = secs - prev;
+                if !(-BACKWARD_DISCONTINUITY_SECS..=FORWARD_DISCONTINUITY_SECS).contains(&delta) {
+                    let bridged = prev + self.last_step;
+                    self.discontinuity_offset += bridged - secs;
+                    self.enter_segment(raw as f64 * self.time_base, bridged);
+                    let secs =
+                        raw as f64 * self.time_base - self.origin_secs + self.discontinuity_offset;
+                    log::warn!(
+                        "[Timeline] Timestamp discontinuity of {:.3}s, continuing at {:.3}s",
+                        delta,
+                        secs
+                    );
+                    secs
+                } else {
+                    if delta > 0.0 {
+                        self.last_step = delta;
+                    }
+                    secs
+                }
+            }
+            _ => secs,
+        };
+
+        self.last_secs = Some(secs);
+        secs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HZ: i64 = 90_000;
+    const FRAME: i64 = HZ / 25;
+
+    fn clock(origin_secs: f64, allow_discontinuities: bool, timeline: &TimelineMap) -> StreamClock {
+        StreamClock::new(
+            ffmpeg::Rational(1, HZ as i32),
+            origin_secs,
+            33,
+            allow_discontinuities,
+            timeline.clone(),
+        )
+    }
+
+    /// Feeds `timestamps` through `clock` and checks each against the expected seconds.
+    fn assert_times(clock: &mut StreamClock, cases: &[(i64, f64)]) {
+        for &(ts, expected) in cases {
+            let secs = clock.to_secs(ts);
+            assert!(
+                (secs - expected).abs() < 1e-9,
+                "{} mapped to {}, expected {}",
+                ts,
+                secs,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn unwraps_the_33_bit_clock() {
+        let period = 1i64 << 33;
+        let start = period - HZ;
+        for allow_discontinuities in [false, true] {
+            let timeline = TimelineMap::default();
+            let mut clock = clock(start as f64 / HZ as f64, allow_discontinuities, &timeline);
+            assert_times(
+                &mut clock,
+                &[
+                    (start, 0.0),
+                    (period - HZ / 2, 0.5),
+                    (0, 1.0),
+                    // Late frame from before the wrap
+                    (period - HZ / 4, 0.75),
+                    (HZ, 2.0),
+                ],
+            );
+            // Seeking maps back to the counter value after the wrap
+            let raw = timeline.raw_secs(1.5, clock.origin_secs);
+            assert!((raw - 0.5).abs() < 1e-6, "1.5 -> {}", raw);
+        }
+    }
+
+    #[test]
+    fn bridges_discontinuities_by_one_frame_step() {
+        let jump = 100 * HZ;
+        let cases = [
+            // Without discontinuity handling the jump goes through as is
+            (
+                false,
+                vec![
+                    (0, 0.0),
+                    (FRAME, 0.04),
+                    (jump, 100.0),
+                    (jump + FRAME, 100.04),
+                ],
+            ),
+            (
+                true,
+                vec![
+                    (0, 0.0),
+                    (FRAME, 0.04),
+                    (2 * FRAME, 0.08),
+                    (jump, 0.12),
+                    (jump + FRAME, 0.16),
+                    // Backwards past the tolerance
+                    (0, 0.20),
+                    (FRAME, 0.24),
+                ],
+            ),
+        ];
+        for (allow_discontinuities, times) in cases {
+            let timeline = TimelineMap::default();
+            let mut clock = clock(0.0, allow_discontinuities, &timeline);
+            assert_times(&mut clock, &times);
+        }
+    }
+
+    #[test]
+    fn shares_breaks_between_streams_and_seeks() {
+        let jump = 100 * HZ;
+        let timeline = TimelineMap::default();
+        let mut video = clock(0.0, true, &timeline);
+        assert_times(
+            &mut video,
+            &[(0, 0.0), (FRAME, 0.04), (2 * FRAME, 0.08), (jump, 0.12)],
+        );
+
+        // Audio crossing the same break, with a different packet step, lands on
+        // the correction video recorded
+        let mut audio = clock(0.0, true, &timeline);
+        assert_times(
+            &mut audio,
+            &[(0, 0.0), (HZ / 50, 0.02), (jump + HZ / 50, 0.14)],
+        );
+
+        let cases = [(0.05, 0.05), (0.12, 100.0), (0.14, 100.02)];
+        for (secs, raw) in cases {
+            let mapped = timeline.raw_secs(secs, 0.0);
+            assert!((mapped - raw).abs() < 1e-9, "{} -> {}", secs, mapped);
+        }
+
+        // After a seek the first timestamp picks the segment it belongs to
+        let cases = [(0.15, jump + 2 * FRAME, 0.20), (0.05, FRAME, 0.04)];
+        for (target, ts, expected) in cases {
+            video.reset(target);
+            assert_times(&mut video, &[(ts, expected)]);
+        }
+    }
+}
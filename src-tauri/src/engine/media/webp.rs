@@ -0,0 +1,196 @@
+use super::animation::frame_delay_ms;
+use ffmpeg_next::util::frame::video::Video;
+use image_webp::{DecodingError, WebPDecoder};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+/// Frames of an animated WebP, composited by the `image-webp` decoder. FFmpeg 6 only
+/// decodes still WebP, so these bypass it.
+pub struct WebpAnimation {
+    path: PathBuf,
+    decoder: WebPDecoder<BufReader<File>>,
+    // One composited canvas, RGBA or RGB depending on the file
+    canvas: Vec<u8>,
+    // Start of the next frame on the timeline, in seconds
+    next_start: f64,
+    // Frames ending at or before this are decoded (to build up the canvas) but not shown
+    skip_until: Option<f64>,
+}
+
+fn open_decoder(path: &Path) -> anyhow::Result<WebPDecoder<BufReader<File>>> {
+    let file = BufReader::new(File::open(path)?);
+    WebPDecoder::new(file).map_err(|e| anyhow::anyhow!("WebP decode error: {}", e))
+}
+
+impl WebpAnimation {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let decoder = open_decoder(path)?;
+        let canvas_size = decoder
+            .output_buffer_size()
+            .ok_or_else(|| anyhow::anyhow!("WebP canvas too large"))?;
+        let (width, height) = decoder.dimensions();
+        log::info!(
+            "[WebP] {}x{} animation, {} frames, alpha {}",
+            width,
+            height,
+            decoder.num_frames(),
+            decoder.has_alpha()
+        );
+        Ok(Self {
+            path: path.to_path_buf(),
+            decoder,
+            canvas: vec![0; canvas_size],
+            next_start: 0.0,
+            skip_until: None,
+        })
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.decoder.dimensions()
+    }
+
+    /// Restarts so the next frame returned is the one showing at `time`. Frames only
+    /// exist as changes to the canvas, so everything before it is decoded again.
+    pub fn seek(&mut self, time: f64) -> anyhow::Result<()> {
+        self.decoder = open_decoder(&self.path)?;
+        self.next_start = 0.0;
+        self.skip_until = (time > 0.0).then_some(time);
+        Ok(())
+    }
+
+    /// Decodes the next frame into `frame` (RGBA, sized to `dimensions`) and returns its
+    /// start time, or `None` after the last frame.
+    pub fn next_frame(&mut self, frame: &mut Video) -> anyhow::Result<Option<f64>> {
+        loop {
+            let delay_ms = match self.decoder.read_frame(&mut self.canvas) {
+                Ok(delay_ms) => delay_ms,
+                Err(DecodingError::NoMoreFrames) => return Ok(None),
+                Err(e) => return Err(anyhow::anyhow!("WebP decode error: {}", e)),
+            };
+            let start = self.next_start;
+            self.next_start += frame_delay_ms(delay_ms) as f64 / 1000.0;
+            if self
+                .skip_until
+                .is_some_and(|until| self.next_start <= until)
+            {
+                continue;
+            }
+            self.skip_until = None;
+            self.copy_canvas(frame);
+            return Ok(Some(start));
+        }
+    }
+
+    fn copy_canvas(&self, frame: &mut Video) {
+        let (width, height) = self.dimensions();
+        let channels = if self.decoder.has_alpha() { 4 } else { 3 };
+        let row_bytes = width as usize * channels;
+        let stride = frame.stride(0);
+        let data = frame.data_mut(0);
+        for (y, src) in self
+            .canvas
+            .chunks_exact(row_bytes)
+            .take(height as usize)
+            .enumerate()
+        {
+            let dst = &mut data[y * stride..y * stride + width as usize * 4];
+            if channels == 4 {
+                dst.copy_from_slice(src);
+            } else {
+                for (d, s) in dst.chunks_exact_mut(4).zip(src.chunks_exact(3)) {
+                    d[..3].copy_from_slice(s);
+                    d[3] = 255;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffmpeg_next::format::Pixel;
+    use image_webp::{ColorType, WebPEncoder};
+
+    fn chunk(data: &mut Vec<u8>, kind: &[u8], body: &[u8]) {
+        data.extend(kind);
+        data.extend((body.len() as u32).to_le_bytes());
+        data.extend(body);
+        if body.len() % 2 == 1 {
+            data.push(0);
+        }
+    }
+
+    /// Writes a 2x2 animated WebP with one solid RGBA frame per (colour, delay) and
+    /// returns its path.
+    fn write_animation(test: &str, frames: &[([u8; 4], u32)]) -> PathBuf {
+        let mut chunks = Vec::new();
+        // Animated, with alpha, on a 2x2 canvas
+        chunk(&mut chunks, b"VP8X", &[0x12, 0, 0, 0, 1, 0, 0, 1, 0, 0]);
+        chunk(&mut chunks, b"ANIM", &[0, 0, 0, 0, 0, 0]);
+        for (colour, delay_ms) in frames {
+            let mut still = Vec::new();
+            WebPEncoder::new(&mut still)
+                .encode(&colour.repeat(4), 2, 2, ColorType::Rgba8)
+                .unwrap();
+            // Position, size - 1, delay and no blending, then the still's VP8L chunk
+            let mut frame = vec![0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 0, 0];
+            frame.extend(&delay_ms.to_le_bytes()[..3]);
+            frame.push(0x02);
+            frame.extend(&still[12..]);
+            chunk(&mut chunks, b"ANMF", &frame);
+        }
+
+        let mut data = b"RIFF".to_vec();
+        data.extend((chunks.len() as u32 + 4).to_le_bytes());
+        data.extend(b"WEBP");
+        data.extend(chunks);
+
+        let path =
+            std::env::temp_dir().join(format!("kenichi-webp-{}-{}.webp", test, std::process::id()));
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    /// (start, first pixel) of every frame left in `animation`.
+    fn drain(animation: &mut WebpAnimation) -> Vec<(f64, [u8; 4])> {
+        let (width, height) = animation.dimensions();
+        let mut frame = Video::new(Pixel::RGBA, width, height);
+        let mut shown = Vec::new();
+        while let Some(start) = animation.next_frame(&mut frame).unwrap() {
+            let pixel = frame.data(0)[..4].try_into().unwrap();
+            shown.push(((start * 1000.0).round() / 1000.0, pixel));
+        }
+        shown
+    }
+
+    #[test]
+    fn plays_and_seeks_frames_on_their_own_timing() {
+        let red = [255, 0, 0, 255];
+        let green = [0, 255, 0, 255];
+        let blue = [0, 0, 255, 128];
+        // The 10 ms frame is shown for 100 ms, as browsers do
+        let path = write_animation("timing", &[(red, 40), (green, 10), (blue, 60)]);
+        let mut animation = WebpAnimation::open(&path).unwrap();
+        assert_eq!(animation.dimensions(), (2, 2));
+
+        assert_eq!(
+            drain(&mut animation),
+            [(0.0, red), (0.04, green), (0.14, blue)]
+        );
+
+        // Each seek resumes with the frame on screen at the target
+        let cases = [
+            (0.0, vec![(0.0, red), (0.04, green), (0.14, blue)]),
+            (0.05, vec![(0.04, green), (0.14, blue)]),
+            (0.14, vec![(0.14, blue)]),
+            (1.0, vec![]),
+        ];
+        for (time, expected) in cases {
+            animation.seek(time).unwrap();
+            assert_eq!(drain(&mut animation), expected, "after seeking to {}", time);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
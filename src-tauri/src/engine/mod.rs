@@ -23,22 +23,39 @@ impl Engine {
                 is_playing: Arc::new(Mutex::new(false)),
                 session_id: Arc::new(Mutex::new(0)),
                 audio_producer: Arc::new(Mutex::new(None)),
+                audio_format: Arc::new(Mutex::new(AudioOutputFormat::default())),
                 volume: Arc::new(std::sync::atomic::AtomicU32::new(1000)), // 1.0 * 1000
+                downmix_mode: Arc::new(Mutex::new(DownmixMode::ItuBs775)),
+                resample_quality: Arc::new(Mutex::new(ResampleQuality::Medium)),
+                deinterlace_mode: Arc::new(Mutex::new(DeinterlaceMode::Auto)),
+                video_filter: Arc::new(Mutex::new(None)),
                 seek_target: Arc::new(Mutex::new(None)),
                 sync_mode: Arc::new(Mutex::new(SyncMode::Realtime)),
+                seek_mode: Arc::new(Mutex::new(SeekMode::Precise)),
+                media_streams: Arc::new(Mutex::new(Vec::new())),
+                video_stream_request: Arc::new(Mutex::new(None)),
+                audio_stream_request: Arc::new(Mutex::new(None)),
+                subtitle_request: Arc::new(Mutex::new(None)),
+                subtitle_file: Arc::new(Mutex::new(None)),
+                subtitle_offset: Arc::new(Mutex::new(0.0)),
+                chapters: Arc::new(Mutex::new(Vec::new())),
+                chapter_request: Arc::new(Mutex::new(None)),
+                sequence_frame_rate: Arc::new(Mutex::new(24.0)),
+                image_sequence: Arc::new(Mutex::new(None)),
+                animation_loop: Arc::new(Mutex::new(AnimationLoop::Forever)),
+                follow_mode: Arc::new(Mutex::new(false)),
+                follow_idle_timeout: Arc::new(Mutex::new(DEFAULT_FOLLOW_IDLE_TIMEOUT)),
             },
             _audio_session: Arc::new(Mutex::new(None)),
         }
     }
 
     pub fn init_audio(&self) -> anyhow::Result<()> {
-        use ringbuf::HeapRb;
+        // The session sizes the ring buffer for the device's negotiated rate and channels
+        let (session, producer) = output::AudioSession::new(self.state.volume.clone())?;
 
-        // Create RingBuffer for 2 seconds of audio (48000 samples * 2 channels * 2s safety)
-        let rb = HeapRb::<f32>::new(192000);
-        let (producer, consumer) = rb.split();
-
-        let session = output::AudioSession::new(self.state.volume.clone(), consumer)?;
+        // Decoders render straight into the device format
+        *self.state.audio_format.lock().unwrap() = session.format().clone();
 
         // Connect the producer to the engine state for the decoder to use
         let mut producer_guard = self.state.audio_producer.lock().unwrap();
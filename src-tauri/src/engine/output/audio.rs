@@ -1,9 +1,15 @@
+use super::super::types::AudioOutputFormat;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample};
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
+// Seconds of audio the ring buffer can hold at the negotiated rate and channel count
+const RING_BUFFER_SECONDS: usize = 2;
+
 pub struct AudioSession {
     _stream: Box<dyn StreamTrait>,
+    format: AudioOutputFormat,
 }
 
 // CPAL Stream is not Send on some platforms (like Windows WASAPI) because of COM/COINIT.
@@ -14,64 +20,108 @@ unsafe impl Send for AudioSession {}
 unsafe impl Sync for AudioSession {}
 
 impl AudioSession {
+    /// Opens the default output device with its preferred configuration and returns the
+    /// session together with the producer end of a ring buffer sized for that configuration.
     pub fn new(
         volume: Arc<std::sync::atomic::AtomicU32>,
-        mut consumer: ringbuf::HeapConsumer<f32>,
-    ) -> anyhow::Result<Self> {
+    ) -> anyhow::Result<(Self, ringbuf::HeapProducer<f32>)> {
         let host = cpal::default_host();
         let device = host
             .default_output_device()
-            .expect("no output device available");
+            .ok_or_else(|| anyhow::anyhow!("No output device available"))?;
         let device_name = device.name().unwrap_or_else(|_| "unknown".to_string());
         log::info!("[Audio] Output device selected: {}", device_name);
 
-        let config = device
-            .default_output_config()
-            .expect("no default output config");
+        let supported = device.default_output_config()?;
+        let sample_format = supported.sample_format();
+        let config: cpal::StreamConfig = supported.into();
 
-        let volume_clone = volume.clone();
-        let mut underrun_counter = 0;
-        let mut callback_counter = 0u64;
+        let format = AudioOutputFormat {
+            sample_rate: config.sample_rate.0,
+            channels: config.channels,
+            sample_format: sample_format.to_string(),
+        };
+        log::info!(
+            "[Audio] Negotiated output: {}Hz, {} channels, {}",
+            format.sample_rate,
+            format.channels,
+            format.sample_format
+        );
 
-        let stream = device.build_output_stream(
-            &config.into(),
-            move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                // Lock-free volume read
-                let vol_int = volume_clone.load(Ordering::Relaxed);
-                let vol = vol_int as f32 / 1000.0;
-                let mut underrun_occurred = false;
+        let rb = ringbuf::HeapRb::<f32>::new(
+            format.sample_rate as usize * format.channels as usize * RING_BUFFER_SECONDS,
+        );
+        let (producer, consumer) = rb.split();
 
-                // Diagnostic: Log occasionally to prove audio thread is alive
-                callback_counter += 1;
-                if callback_counter % 200 == 0 { // Approx every ~2-4 seconds depending on buffer size
-                     // log::debug!("[Audio] Callback active. Volume: {}", vol);
-                     // Note: println in realtime thread is bad practice, but okay for temporary debug
-                }
-
-                for sample in data.iter_mut() {
-                    match consumer.pop() {
-                        Some(s) => *sample = s * vol,
-                        None => {
-                            *sample = 0.0;
-                            underrun_occurred = true;
-                        }
-                    }
-                }
-                if underrun_occurred {
-                    underrun_counter += 1;
-                    if underrun_counter % 200 == 0 {
-                        eprintln!("[Audio] Buffer Underrun (Starvation) x{}", underrun_counter);
-                    }
-                }
-            },
-            |err| eprintln!("[Audio] Stream error: {}", err),
-            None,
-        )?;
+        let stream = match sample_format {
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, volume, consumer)?,
+            SampleFormat::F64 => build_stream::<f64>(&device, &config, volume, consumer)?,
+            SampleFormat::I8 => build_stream::<i8>(&device, &config, volume, consumer)?,
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, volume, consumer)?,
+            SampleFormat::I32 => build_stream::<i32>(&device, &config, volume, consumer)?,
+            SampleFormat::U8 => build_stream::<u8>(&device, &config, volume, consumer)?,
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, volume, consumer)?,
+            SampleFormat::U32 => build_stream::<u32>(&device, &config, volume, consumer)?,
+            other => anyhow::bail!("Unsupported output sample format: {}", other),
+        };
 
         stream.play()?;
 
-        Ok(Self {
-            _stream: Box::new(stream),
-        })
+        Ok((
+            Self {
+                _stream: Box::new(stream),
+                format,
+            },
+            producer,
+        ))
+    }
+
+    pub fn format(&self) -> &AudioOutputFormat {
+        &self.format
     }
 }
+
+/// Builds the output stream for device sample type `T`, converting the f32 ring buffer
+/// samples on the fly.
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    volume: Arc<std::sync::atomic::AtomicU32>,
+    mut consumer: ringbuf::HeapConsumer<f32>,
+) -> anyhow::Result<cpal::Stream>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let mut underrun_counter = 0;
+
+    let stream = device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            // Lock-free volume read
+            let vol_int = volume.load(Ordering::Relaxed);
+            let vol = vol_int as f32 / 1000.0;
+            let mut underrun_occurred = false;
+
+            for sample in data.iter_mut() {
+                let value = match consumer.pop() {
+                    Some(s) => s * vol,
+                    None => {
+                        underrun_occurred = true;
+                        0.0
+                    }
+                };
+                *sample = T::from_sample(value);
+            }
+            if underrun_occurred {
+                underrun_counter += 1;
+                if underrun_counter % 200 == 0 {
+                    eprintln!("[Audio] Buffer Underrun (Starvation) x{}", underrun_counter);
+                }
+            }
+        },
+        |err| eprintln!("[Audio] Stream error: {}", err),
+        None,
+    )?;
+
+    Ok(stream)
+}
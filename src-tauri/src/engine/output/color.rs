@@ -0,0 +1,75 @@
+use super::super::types::{ColorMatrix, ColorRange, HdrMetadata, TransferFunction};
+
+// Assumed peak for PQ without light level metadata, and HLG's BT.2100 reference display
+const DEFAULT_HDR_PEAK_NITS: f32 = 1000.0;
+
+/// Luma coefficients (Kr, Kb) of a YUV colour matrix.
+pub fn luma_coefficients(matrix: ColorMatrix) -> (f32, f32) {
+    match matrix {
+        ColorMatrix::Rgb | ColorMatrix::Bt601 => (0.299, 0.114),
+        ColorMatrix::Bt709 => (0.2126, 0.0722),
+        // Constant-luminance BT.2020 is rare enough to share the NCL matrix
+        ColorMatrix::Bt2020 => (0.2627, 0.0593),
+        ColorMatrix::Smpte240m => (0.212, 0.087),
+    }
+}
+
+/// Rows of the matrix converting normalised Y'CbCr samples to R'G'B'. Each row is
+/// `[y, cb, cr, offset]`, so a channel is `dot(row, [y, cb, cr, 1])`; the offsets fold in
+/// the black level and chroma centre. Samples hold `bit_depth`-bit values in the low bits
+/// of a `container_bits`-bit texel, which the GPU normalises against the container.
+pub fn yuv_to_rgb_rows(
+    matrix: ColorMatrix,
+    range: ColorRange,
+    bit_depth: u32,
+    container_bits: u32,
+) -> [[f32; 4]; 3] {
+    let (kr, kb) = luma_coefficients(matrix);
+    let kg = 1.0 - kr - kb;
+    let container_max = ((1u64 << container_bits) - 1) as f32;
+    // Code values scale with the bit depth: 8-bit 16 is 10-bit 64
+    let step = (1u64 << bit_depth) as f32 / 256.0;
+    // Limited range puts black at 16 and white at 235, chroma spanning 16..240
+    let (y_scale, y_offset, c_scale) = match range {
+        ColorRange::Full => {
+            let code_max = ((1u64 << bit_depth) - 1) as f32;
+            (container_max / code_max, 0.0, container_max / code_max)
+        }
+        ColorRange::Limited => (
+            container_max / (219.0 * step),
+            -16.0 / 219.0,
+            container_max / (224.0 * step),
+        ),
+    };
+    let chroma_centre = 128.0 * step / container_max;
+
+    let r_cr = 2.0 * (1.0 - kr) * c_scale;
+    let g_cb = -2.0 * kb * (1.0 - kb) / kg * c_scale;
+    let g_cr = -2.0 * kr * (1.0 - kr) / kg * c_scale;
+    let b_cb = 2.0 * (1.0 - kb) * c_scale;
+
+    [
+        [y_scale, 0.0, r_cr, y_offset - r_cr * chroma_centre],
+        [
+            y_scale,
+            g_cb,
+            g_cr,
+            y_offset - (g_cb + g_cr) * chroma_centre,
+        ],
+        [y_scale, b_cb, 0.0, y_offset - b_cb * chroma_centre],
+    ]
+}
+
+/// Brightest level to tone map from: MaxCLL when known, else the mastering display's peak.
+pub fn source_peak_nits(transfer: TransferFunction, hdr: &HdrMetadata) -> f32 {
+    match transfer {
+        TransferFunction::Pq => hdr
+            .max_cll
+            .or(hdr.mastering_max_nits)
+            .filter(|nits| *nits > 0.0)
+            .unwrap_or(DEFAULT_HDR_PEAK_NITS),
+        TransferFunction::Hlg => DEFAULT_HDR_PEAK_NITS,
+        // SDR white is the target peak itself, so nothing is compressed
+        TransferFunction::Sdr => 0.0,
+    }
+}
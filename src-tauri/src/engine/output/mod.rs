@@ -1,5 +1,9 @@
 pub mod audio;
+pub mod color;
 pub mod renderer;
+pub mod subtitle;
 
 pub use audio::*;
+pub use color::*;
 pub use renderer::*;
+pub use subtitle::*;
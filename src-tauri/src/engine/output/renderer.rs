@@ -1,5 +1,98 @@
 use wgpu::*;
 use std::sync::Arc;
+use bytemuck::Zeroable;
+
+/// Per-draw shader parameters (`VideoParams` in shader.wgsl).
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct VideoParams {
+    rotation: u32,
+    // 0: RGBA (sRGB-decoded), 1: separate Y/U/V planes, 2: Y plus interleaved UV,
+    // 3: RGBA as stored
+    plane_format: u32,
+    // 0: SDR, 1: PQ, 2: HLG
+    transfer: u32,
+    // 0: Hable, 1: BT.2390, 2: Reinhard
+    tone_map: u32,
+    // Y'CbCr to R'G'B' rows, see `yuv_to_rgb_rows`
+    yuv_to_rgb: [[f32; 4]; 3],
+    source_peak_nits: f32,
+    target_peak_nits: f32,
+    // Non-zero to convert BT.2020 primaries to BT.709
+    wide_gamut: u32,
+    // 0: straight, 1: premultiplied, 2: opaque
+    alpha_mode: u32,
+    // 0: none (left to blending), 1: checkerboard, 2: background_color
+    background: u32,
+    // Non-zero to show the alpha channel as greyscale
+    alpha_only: u32,
+    _padding: [u32; 2],
+    // Linear RGB
+    background_color: [f32; 4],
+}
+
+fn shader_plane_format(layout: PlaneLayout) -> u32 {
+    match layout {
+        PlaneLayout::Rgba => 0,
+        PlaneLayout::Yuv420p
+        | PlaneLayout::Yuv422p
+        | PlaneLayout::Yuv420p16
+        | PlaneLayout::Yuv422p16 => 1,
+        PlaneLayout::Nv12 | PlaneLayout::P010 => 2,
+        // Rgba16Unorm has no sRGB variant, so the samples arrive undecoded
+        PlaneLayout::Rgba16 => 3,
+    }
+}
+
+fn shader_transfer(transfer: TransferFunction) -> u32 {
+    match transfer {
+        TransferFunction::Sdr => 0,
+        TransferFunction::Pq => 1,
+        TransferFunction::Hlg => 2,
+    }
+}
+
+fn shader_tone_map(operator: ToneMapOperator) -> u32 {
+    match operator {
+        ToneMapOperator::Hable => 0,
+        ToneMapOperator::Bt2390 => 1,
+        ToneMapOperator::Reinhard => 2,
+    }
+}
+
+fn shader_alpha_mode(has_alpha: bool, mode: AlphaMode) -> u32 {
+    match (has_alpha, mode) {
+        (false, _) => 2,
+        (true, AlphaMode::Straight) => 0,
+        (true, AlphaMode::Premultiplied) => 1,
+    }
+}
+
+fn srgb_to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Shader background selector and linear colour for `mode`.
+fn shader_background(mode: BackgroundMode) -> (u32, [f32; 4]) {
+    let solid = |rgb: [u8; 3]| {
+        let [r, g, b] = rgb.map(srgb_to_linear);
+        (2, [r, g, b, 1.0])
+    };
+    match mode {
+        BackgroundMode::Checkerboard => (1, [0.0; 4]),
+        BackgroundMode::Solid(rgb) => solid(rgb),
+        BackgroundMode::Black => solid([0, 0, 0]),
+        BackgroundMode::White => solid([255, 255, 255]),
+    }
+}
+
+// SDR white for HDR sources: BT.2408's HDR reference white lands at full scale
+const DEFAULT_TARGET_PEAK_NITS: f32 = 203.0;
 
 pub struct Renderer {
     pub surface: Surface<'static>,
@@ -10,17 +103,49 @@ pub struct Renderer {
     pub pipeline: RenderPipeline,
     pub bind_group_layout: BindGroupLayout,
     pub sampler: Sampler,
-    pub video_texture: Option<Texture>,
-    pub video_texture_view: Option<TextureView>,
+    // One texture per plane of the current frame layout
+    pub video_planes: Vec<Texture>,
+    pub video_layout: Option<PlaneLayout>,
     pub video_bind_group: Option<BindGroup>,
-    // Buffer to handle stride alignment padding
-    pub padding_buffer: Vec<u8>,
+    // Bound to plane slots the current layout doesn't use
+    pub placeholder_view: TextureView,
     pub container_viewport: Option<Rect>,
     pub current_aspect_mode: AspectMode,
     pub last_video_size: Option<(u32, u32)>,
+    // Display geometry of the current video: clockwise rotation and sample aspect ratio
+    pub video_rotation: u32,
+    pub pixel_aspect: f32,
+    // Whether 16-bit plane layouts can be uploaded
+    pub high_bit_depth: bool,
+    video_params: VideoParams,
+    pub video_params_buffer: Buffer,
+    // HDR to SDR tone mapping
+    pub tone_map_operator: ToneMapOperator,
+    pub target_peak_nits: f32,
+    // Transparency: how alpha is read, what shows through it, and the alpha-only view
+    pub alpha_mode: AlphaMode,
+    pub background_mode: BackgroundMode,
+    pub alpha_only: bool,
+    video_has_alpha: bool,
+    // Identity parameters for overlays
+    pub overlay_params_buffer: Buffer,
+    // Subtitle overlay, composited over the video in a second pass
+    pub subtitle_pipeline: RenderPipeline,
+    pub subtitle_rasterizer: SubtitleRasterizer,
+    pub subtitle_content: Option<SubtitleContent>,
+    pub subtitle_texture: Option<Texture>,
+    pub subtitle_bind_group: Option<BindGroup>,
+    pub subtitle_placement: Option<Rect>,
+    // Viewport size the current overlay was rasterised for
+    pub subtitle_viewport: Option<(f32, f32)>,
 }
 
-use super::super::types::{AspectMode, Rect};
+use super::super::types::{
+    AlphaMode, AspectMode, BackgroundMode, PlaneLayout, Rect, SubtitleContent, ToneMapOperator,
+    TransferFunction, VideoFrame,
+};
+use super::color::{source_peak_nits, yuv_to_rgb_rows};
+use super::subtitle::{SubtitleOverlay, SubtitleRasterizer};
 
 impl Renderer {
     pub async fn new(window: Arc<tauri::Window>) -> anyhow::Result<Self> {
@@ -52,11 +177,25 @@ impl Renderer {
         let limits = adapter.limits();
         eprintln!("[Renderer] Adapter limits determined: {:?}", limits);
 
+        // 16-bit normalised textures carry 10/12-bit video without truncation
+        let high_bit_depth = adapter
+            .features()
+            .contains(Features::TEXTURE_FORMAT_16BIT_NORM);
+        if !high_bit_depth {
+            log::warn!(
+                "[Renderer] No 16-bit texture support, high bit depth video will be shown at 8 bits"
+            );
+        }
+
         let (device, queue) = adapter
             .request_device(
                 &DeviceDescriptor {
                     label: None,
-                    required_features: Features::empty(),
+                    required_features: if high_bit_depth {
+                        Features::TEXTURE_FORMAT_16BIT_NORM
+                    } else {
+                        Features::empty()
+                    },
                     required_limits: limits,
                 },
                 None,
@@ -108,6 +247,37 @@ impl Renderer {
                     ty: BindingType::Sampler(SamplerBindingType::Filtering),
                     count: None,
                 },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Chroma planes for YUV frames
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
             ],
             label: Some("Video Bind Group Layout"),
         });
@@ -118,29 +288,39 @@ impl Renderer {
             push_constant_ranges: &[],
         });
 
-        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: Some("Video Render Pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[],
-            },
-            fragment: Some(FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(ColorTargetState {
-                    format: config.format,
-                    blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
-                    write_mask: ColorWrites::ALL,
-                })],
-            }),
-            primitive: PrimitiveState::default(),
-            depth_stencil: None,
-            multisample: MultisampleState::default(),
-            multiview: None,
-        });
-        eprintln!("[Renderer] Render pipeline created.");
+        let build_pipeline = |label: &str, blend: BlendState| {
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(ColorTargetState {
+                        format: config.format,
+                        blend: Some(blend),
+                        write_mask: ColorWrites::ALL,
+                    })],
+                }),
+                primitive: PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                multiview: None,
+            })
+        };
+
+        let pipeline = build_pipeline(
+            "Video Render Pipeline",
+            BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+        );
+        // Subtitle overlays carry straight alpha so their sRGB colours decode correctly
+        let subtitle_pipeline =
+            build_pipeline("Subtitle Render Pipeline", BlendState::ALPHA_BLENDING);
+        eprintln!("[Renderer] Render pipelines created.");
 
         let sampler = device.create_sampler(&SamplerDescriptor {
             address_mode_u: AddressMode::ClampToEdge,
@@ -152,6 +332,35 @@ impl Renderer {
             ..Default::default()
         });
 
+        let create_params_buffer = |label: &str| {
+            let buffer = device.create_buffer(&BufferDescriptor {
+                label: Some(label),
+                size: std::mem::size_of::<VideoParams>() as u64,
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            queue.write_buffer(&buffer, 0, bytemuck::bytes_of(&VideoParams::zeroed()));
+            buffer
+        };
+        let video_params_buffer = create_params_buffer("Video Params");
+        let overlay_params_buffer = create_params_buffer("Overlay Params");
+
+        let placeholder = device.create_texture(&TextureDescriptor {
+            size: Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING,
+            label: Some("Placeholder Plane"),
+            view_formats: &[],
+        });
+        let placeholder_view = placeholder.create_view(&TextureViewDescriptor::default());
+
         eprintln!("[Renderer] Initialization complete.");
         Ok(Self {
             surface,
@@ -162,13 +371,32 @@ impl Renderer {
             pipeline,
             bind_group_layout,
             sampler,
-            video_texture: None,
-            video_texture_view: None,
+            video_planes: Vec::new(),
+            video_layout: None,
             video_bind_group: None,
-            padding_buffer: Vec::new(),
+            placeholder_view,
             container_viewport: None,
             current_aspect_mode: AspectMode::Fit,
             last_video_size: None,
+            video_rotation: 0,
+            pixel_aspect: 1.0,
+            high_bit_depth,
+            video_params: VideoParams::zeroed(),
+            video_params_buffer,
+            tone_map_operator: ToneMapOperator::Bt2390,
+            target_peak_nits: DEFAULT_TARGET_PEAK_NITS,
+            alpha_mode: AlphaMode::Straight,
+            background_mode: BackgroundMode::Checkerboard,
+            alpha_only: false,
+            video_has_alpha: false,
+            overlay_params_buffer,
+            subtitle_pipeline,
+            subtitle_rasterizer: SubtitleRasterizer::new(),
+            subtitle_content: None,
+            subtitle_texture: None,
+            subtitle_bind_group: None,
+            subtitle_placement: None,
+            subtitle_viewport: None,
         })
     }
 
@@ -190,10 +418,272 @@ impl Renderer {
     }
 
     pub fn clear_video(&mut self) {
-        self.video_texture = None;
-        self.video_texture_view = None;
+        self.video_planes.clear();
+        self.video_layout = None;
         self.video_bind_group = None;
         self.last_video_size = None;
+        self.set_orientation(0, 1.0);
+    }
+
+    /// Sets how upcoming frames are displayed: `rotation` is clockwise degrees (0, 90, 180
+    /// or 270) and `pixel_aspect` the sample aspect ratio.
+    pub fn set_orientation(&mut self, rotation: u32, pixel_aspect: f32) {
+        let pixel_aspect = if pixel_aspect.is_finite() && pixel_aspect > 0.0 {
+            pixel_aspect
+        } else {
+            1.0
+        };
+        self.set_video_params(VideoParams {
+            rotation,
+            ..self.video_params
+        });
+        self.video_rotation = rotation;
+        self.pixel_aspect = pixel_aspect;
+    }
+
+    /// Selects how PQ and HLG video is mapped into SDR, peaking at `target_peak_nits`.
+    pub fn set_tone_mapping(&mut self, operator: ToneMapOperator, target_peak_nits: f32) {
+        self.tone_map_operator = operator;
+        self.target_peak_nits = target_peak_nits;
+        self.set_video_params(VideoParams {
+            tone_map: shader_tone_map(operator),
+            target_peak_nits,
+            ..self.video_params
+        });
+    }
+
+    /// Sets what shows through transparent video.
+    pub fn set_background(&mut self, mode: BackgroundMode) {
+        self.background_mode = mode;
+        let (background, background_color) = shader_background(mode);
+        self.set_video_params(VideoParams {
+            background,
+            background_color,
+            ..self.video_params
+        });
+    }
+
+    /// Sets whether the colour of transparent video is premultiplied by its alpha.
+    pub fn set_alpha_mode(&mut self, mode: AlphaMode) {
+        self.alpha_mode = mode;
+        self.set_video_params(VideoParams {
+            alpha_mode: shader_alpha_mode(self.video_has_alpha, mode),
+            ..self.video_params
+        });
+    }
+
+    /// Shows the alpha channel as greyscale (white opaque, black transparent) instead of
+    /// the picture.
+    pub fn set_alpha_only(&mut self, alpha_only: bool) {
+        self.alpha_only = alpha_only;
+        self.set_video_params(VideoParams {
+            alpha_only: alpha_only as u32,
+            ..self.video_params
+        });
+    }
+
+    /// Uploads `params` for the video pass if they changed.
+    fn set_video_params(&mut self, params: VideoParams) {
+        if params != self.video_params {
+            self.queue
+                .write_buffer(&self.video_params_buffer, 0, bytemuck::bytes_of(&params));
+            self.video_params = params;
+        }
+    }
+
+    /// Creates the plane textures and bind group for `layout` frames of this size.
+    fn create_video_planes(&mut self, layout: PlaneLayout, width: u32, height: u32) {
+        let formats: &[TextureFormat] = match layout {
+            PlaneLayout::Rgba => &[TextureFormat::Rgba8UnormSrgb],
+            PlaneLayout::Rgba16 => &[TextureFormat::Rgba16Unorm],
+            PlaneLayout::Yuv420p | PlaneLayout::Yuv422p => &[TextureFormat::R8Unorm; 3],
+            PlaneLayout::Nv12 => &[TextureFormat::R8Unorm, TextureFormat::Rg8Unorm],
+            PlaneLayout::Yuv420p16 | PlaneLayout::Yuv422p16 => &[TextureFormat::R16Unorm; 3],
+            PlaneLayout::P010 => &[TextureFormat::R16Unorm, TextureFormat::Rg16Unorm],
+        };
+        let planes: Vec<Texture> = formats
+            .iter()
+            .enumerate()
+            .map(|(index, format)| {
+                let (plane_width, plane_height) = layout.plane_size(index, width, height);
+                self.device.create_texture(&TextureDescriptor {
+                    size: Extent3d {
+                        width: plane_width,
+                        height: plane_height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: *format,
+                    usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                    label: Some("Video Plane"),
+                    view_formats: &[],
+                })
+            })
+            .collect();
+        let views: Vec<TextureView> = planes
+            .iter()
+            .map(|t| t.create_view(&TextureViewDescriptor::default()))
+            .collect();
+        let view = |index: usize| views.get(index).unwrap_or(&self.placeholder_view);
+
+        let bind_group = self.device.create_bind_group(&BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(view(0)),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&self.sampler),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: self.video_params_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(view(1)),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: BindingResource::TextureView(view(2)),
+                },
+            ],
+            label: Some("Video Bind Group"),
+        });
+
+        self.video_planes = planes;
+        self.video_layout = Some(layout);
+        self.video_bind_group = Some(bind_group);
+    }
+
+    /// Shows `content` over the video until replaced; `None` hides the overlay.
+    pub fn set_subtitle(&mut self, content: Option<SubtitleContent>) {
+        self.subtitle_content = content;
+        self.subtitle_viewport = None;
+        self.refresh_subtitle();
+    }
+
+    /// Re-rasterises the overlay when the cue changed or the video viewport was resized.
+    fn refresh_subtitle(&mut self) {
+        let viewport = self
+            .calculate_actual_viewport()
+            .map(|rect| (rect.width, rect.height));
+        if self.subtitle_viewport.is_some() && self.subtitle_viewport == viewport {
+            return;
+        }
+        self.subtitle_viewport = viewport;
+
+        let overlay = match (&self.subtitle_content, viewport) {
+            (Some(content), Some(viewport)) => {
+                self.subtitle_rasterizer.rasterize(content, viewport)
+            }
+            _ => None,
+        };
+        match overlay {
+            Some(overlay) => self.upload_subtitle(&overlay),
+            None => {
+                self.subtitle_texture = None;
+                self.subtitle_bind_group = None;
+                self.subtitle_placement = None;
+            }
+        }
+    }
+
+    fn upload_subtitle(&mut self, overlay: &SubtitleOverlay) {
+        let texture_size = Extent3d {
+            width: overlay.width,
+            height: overlay.height,
+            depth_or_array_layers: 1,
+        };
+        let texture = self.device.create_texture(&TextureDescriptor {
+            size: texture_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            label: Some("Subtitle Overlay"),
+            view_formats: &[],
+        });
+
+        // Tightly packed rows; like the video planes, write_texture takes them as they are
+        self.queue.write_texture(
+            ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            &overlay.rgba,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(overlay.width * 4),
+                rows_per_image: Some(overlay.height),
+            },
+            texture_size,
+        );
+
+        let texture_view = texture.create_view(&TextureViewDescriptor::default());
+        let bind_group = self.device.create_bind_group(&BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&texture_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&self.sampler),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: self.overlay_params_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(&self.placeholder_view),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: BindingResource::TextureView(&self.placeholder_view),
+                },
+            ],
+            label: Some("Subtitle Bind Group"),
+        });
+
+        self.subtitle_texture = Some(texture);
+        self.subtitle_bind_group = Some(bind_group);
+        self.subtitle_placement = Some(overlay.placement);
+    }
+
+    /// Second pass: draws the overlay into its placement within the video viewport.
+    fn draw_subtitle<'a>(&'a self, render_pass: &mut RenderPass<'a>, video_rect: Option<Rect>) {
+        let (bind_group, placement, video) = match (
+            &self.subtitle_bind_group,
+            self.subtitle_placement,
+            video_rect,
+        ) {
+            (Some(b), Some(p), Some(v)) => (b, p, v),
+            _ => return,
+        };
+        let rect = self.safe_clamp(
+            Rect {
+                x: video.x + placement.x * video.width,
+                y: video.y + placement.y * video.height,
+                width: placement.width * video.width,
+                height: placement.height * video.height,
+            },
+            self.config.width as f32,
+            self.config.height as f32,
+        );
+        render_pass.set_viewport(rect.x, rect.y, rect.width, rect.height, 0.0, 1.0);
+        render_pass.set_pipeline(&self.subtitle_pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
     }
 
     fn calculate_actual_viewport(&self) -> Option<Rect> {
@@ -205,8 +695,15 @@ impl Renderer {
             if v_w == 0 || v_h == 0 {
                 container
             } else {
+                // Display aspect: stored size scaled by the SAR, swapped for quarter turns
+                let display_ratio = (v_w as f32 * self.pixel_aspect) / (v_h as f32);
+                let display_ratio = if self.video_rotation % 180 == 90 {
+                    1.0 / display_ratio
+                } else {
+                    display_ratio
+                };
                 let target_ratio = match self.current_aspect_mode {
-                    AspectMode::Fit => (v_w as f32) / (v_h as f32),
+                    AspectMode::Fit => display_ratio,
                     AspectMode::Cinema => 21.0 / 9.0,
                     AspectMode::Classic => 4.0 / 3.0,
                     AspectMode::Wide => 16.0 / 9.0,
@@ -247,9 +744,10 @@ impl Renderer {
         rect
     }
 
-    pub fn render_frame(&mut self, rgba_data: &[u8], width: u32, height: u32, stride: u32) -> anyhow::Result<()> {
+    pub fn render_frame(&mut self, frame: &VideoFrame) -> anyhow::Result<()> {
+        let (width, height) = (frame.width, frame.height);
         self.last_video_size = Some((width, height));
-        if width == 0 || height == 0 || stride == 0 {
+        if width == 0 || height == 0 || frame.planes.len() < frame.layout.plane_count() {
             return Ok(());
         }
         
@@ -267,108 +765,64 @@ impl Renderer {
         };
 
         let view = output.texture.create_view(&TextureViewDescriptor::default());
+        self.refresh_subtitle();
 
-        let texture_size = Extent3d {
-            width,
-            height,
-            depth_or_array_layers: 1,
-        };
-
-        // 1. Reuse or Create Texture
-        let needs_new_texture = self.video_texture.as_ref().map_or(true, |t| {
-            t.width() != width || t.height() != height
-        });
-
-        if needs_new_texture {
-            let texture = self.device.create_texture(&TextureDescriptor {
-                size: texture_size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: TextureDimension::D2,
-                format: TextureFormat::Rgba8UnormSrgb,
-                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
-                label: Some("Video Frame"),
-                view_formats: &[],
-            });
-            
-            let texture_view = texture.create_view(&TextureViewDescriptor::default());
-            let bind_group = self.device.create_bind_group(&BindGroupDescriptor {
-                layout: &self.bind_group_layout,
-                entries: &[
-                    BindGroupEntry {
-                        binding: 0,
-                        resource: BindingResource::TextureView(&texture_view),
-                    },
-                    BindGroupEntry {
-                        binding: 1,
-                        resource: BindingResource::Sampler(&self.sampler),
-                    },
-                ],
-                label: Some("Video Bind Group"),
+        // 1. Reuse or Create Plane Textures
+        let needs_new_textures = self.video_layout != Some(frame.layout)
+            || self.video_planes.first().map_or(true, |t| {
+                t.width() != width || t.height() != height
             });
 
-            self.video_texture = Some(texture);
-            self.video_texture_view = Some(texture_view);
-            self.video_bind_group = Some(bind_group);
+        if needs_new_textures {
+            self.create_video_planes(frame.layout, width, height);
         }
 
-        // 2. Upload Data
-        if let Some(texture) = &self.video_texture {
-            let unaligned_bytes_per_row = stride;
-            let align = 256;
-            let aligned_bytes_per_row = (unaligned_bytes_per_row + align - 1) & !(align - 1);
-
-            if unaligned_bytes_per_row == aligned_bytes_per_row {
-                self.queue.write_texture(
-                    ImageCopyTexture {
-                        texture,
-                        mip_level: 0,
-                        origin: Origin3d::ZERO,
-                        aspect: TextureAspect::All,
-                    },
-                    rgba_data,
-                    ImageDataLayout {
-                        offset: 0,
-                        bytes_per_row: Some(aligned_bytes_per_row),
-                        rows_per_image: Some(height),
-                    },
-                    texture_size,
-                );
-            } else {
-                let required_size = (aligned_bytes_per_row * height) as usize;
-                if self.padding_buffer.len() < required_size {
-                    self.padding_buffer.resize(required_size, 0);
-                }
-
-                for y in 0..height {
-                    let src_start = (y * unaligned_bytes_per_row) as usize;
-                    let src_end = src_start + (width * 4) as usize;
-                    let dst_start = (y * aligned_bytes_per_row) as usize;
-                    
-                    if src_end <= rgba_data.len() && (dst_start + (width * 4) as usize) <= self.padding_buffer.len() {
-                        self.padding_buffer[dst_start..dst_start + (width * 4) as usize]
-                            .copy_from_slice(&rgba_data[src_start..src_end]);
-                    }
-                }
-
-                self.queue.write_texture(
-                    ImageCopyTexture {
-                        texture,
-                        mip_level: 0,
-                        origin: Origin3d::ZERO,
-                        aspect: TextureAspect::All,
-                    },
-                    &self.padding_buffer,
-                    ImageDataLayout {
-                        offset: 0,
-                        bytes_per_row: Some(aligned_bytes_per_row),
-                        rows_per_image: Some(height),
-                    },
-                    texture_size,
-                );
-            }
+        // 2. Upload Planes (write_texture takes any row pitch, so strides pass straight through)
+        for (index, (texture, plane)) in self.video_planes.iter().zip(&frame.planes).enumerate() {
+            let (plane_width, plane_height) = frame.layout.plane_size(index, width, height);
+            self.queue.write_texture(
+                ImageCopyTexture {
+                    texture,
+                    mip_level: 0,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All,
+                },
+                &plane.data,
+                ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(plane.stride),
+                    rows_per_image: Some(plane_height),
+                },
+                Extent3d {
+                    width: plane_width,
+                    height: plane_height,
+                    depth_or_array_layers: 1,
+                },
+            );
         }
 
+        self.video_has_alpha = frame.has_alpha;
+        let (background, background_color) = shader_background(self.background_mode);
+        self.set_video_params(VideoParams {
+            plane_format: shader_plane_format(frame.layout),
+            transfer: shader_transfer(frame.transfer),
+            tone_map: shader_tone_map(self.tone_map_operator),
+            yuv_to_rgb: yuv_to_rgb_rows(
+                frame.matrix,
+                frame.range,
+                frame.bit_depth,
+                frame.layout.container_bits(),
+            ),
+            source_peak_nits: source_peak_nits(frame.transfer, &frame.hdr),
+            target_peak_nits: self.target_peak_nits,
+            wide_gamut: frame.wide_gamut as u32,
+            alpha_mode: shader_alpha_mode(frame.has_alpha, self.alpha_mode),
+            background,
+            background_color,
+            alpha_only: self.alpha_only as u32,
+            ..self.video_params
+        });
+
         // 3. Render
         let mut encoder = self.device.create_command_encoder(&CommandEncoderDescriptor {
             label: Some("Render Encoder"),
@@ -394,7 +848,8 @@ impl Renderer {
                 occlusion_query_set: None,
                 timestamp_writes: None,
             });
-            if let Some(rect) = self.calculate_actual_viewport() {
+            let video_rect = self.calculate_actual_viewport();
+            if let Some(rect) = video_rect {
                 render_pass.set_viewport(rect.x, rect.y, rect.width, rect.height, 0.0, 1.0);
             }
 
@@ -403,6 +858,8 @@ impl Renderer {
                 render_pass.set_bind_group(0, bind_group, &[]);
                 render_pass.draw(0..3, 0..1);
             }
+
+            self.draw_subtitle(&mut render_pass, video_rect);
         }
 
         self.queue.submit(std::iter::once(encoder.finish()));
@@ -422,6 +879,7 @@ impl Renderer {
             Err(e) => return Err(e.into()),
         };
         let view = output.texture.create_view(&TextureViewDescriptor::default());
+        self.refresh_subtitle();
         let mut encoder = self.device.create_command_encoder(&CommandEncoderDescriptor {
             label: Some("Repaint Encoder"),
         });
@@ -446,7 +904,8 @@ impl Renderer {
                 occlusion_query_set: None,
                 timestamp_writes: None,
             });
-            if let Some(rect) = self.calculate_actual_viewport() {
+            let video_rect = self.calculate_actual_viewport();
+            if let Some(rect) = video_rect {
                 render_pass.set_viewport(rect.x, rect.y, rect.width, rect.height, 0.0, 1.0);
             }
 
@@ -455,6 +914,8 @@ impl Renderer {
                 render_pass.set_bind_group(0, bind_group, &[]);
                 render_pass.draw(0..3, 0..1);
             }
+
+            self.draw_subtitle(&mut render_pass, video_rect);
         }
 
         self.queue.submit(std::iter::once(encoder.finish()));
@@ -18,7 +18,248 @@ var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
 
+struct VideoParams {
+    // Clockwise rotation applied on display: 0, 90, 180 or 270
+    rotation: u32,
+    // 0: sRGB-decoded RGBA in t_diffuse, 1: Y/U/V in t_diffuse/t_plane1/t_plane2,
+    // 2: Y plus UV in t_plane1, 3: undecoded RGBA in t_diffuse
+    plane_format: u32,
+    // 0: SDR, 1: PQ, 2: HLG
+    transfer: u32,
+    // 0: Hable, 1: BT.2390, 2: Reinhard
+    tone_map: u32,
+    // Y'CbCr to R'G'B' rows; a channel is dot(row, vec4(y, cb, cr, 1))
+    yuv_to_rgb: array<vec4<f32>, 3>,
+    // Brightest level in the source and the level shown as SDR white
+    source_peak_nits: f32,
+    target_peak_nits: f32,
+    // Non-zero for BT.2020 primaries
+    wide_gamut: u32,
+    // 0: straight, 1: premultiplied, 2: opaque
+    alpha_mode: u32,
+    // 0: none (left to blending), 1: checkerboard, 2: background_color
+    background: u32,
+    // Non-zero to show the alpha channel as greyscale
+    alpha_only: u32,
+    _pad0: u32,
+    _pad1: u32,
+    // Linear RGB
+    background_color: vec4<f32>,
+};
+
+@group(0) @binding(2)
+var<uniform> params: VideoParams;
+
+@group(0) @binding(3)
+var t_plane1: texture_2d<f32>;
+@group(0) @binding(4)
+var t_plane2: texture_2d<f32>;
+
+// Maps a screen coordinate to the texture coordinate of the rotated picture
+fn rotate_uv(uv: vec2<f32>) -> vec2<f32> {
+    switch params.rotation {
+        case 90u: {
+            return vec2<f32>(uv.y, 1.0 - uv.x);
+        }
+        case 180u: {
+            return vec2<f32>(1.0 - uv.x, 1.0 - uv.y);
+        }
+        case 270u: {
+            return vec2<f32>(1.0 - uv.y, uv.x);
+        }
+        default: {
+            return uv;
+        }
+    }
+}
+
+fn yuv_to_rgb(yuv: vec3<f32>) -> vec3<f32> {
+    let p = vec4<f32>(yuv, 1.0);
+    let rgb = vec3<f32>(
+        dot(params.yuv_to_rgb[0], p),
+        dot(params.yuv_to_rgb[1], p),
+        dot(params.yuv_to_rgb[2], p),
+    );
+    return clamp(rgb, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// The RGBA path samples an sRGB texture, which decodes for us; match it for YUV
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    let low = c / 12.92;
+    let high = pow((c + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, c <= vec3<f32>(0.04045));
+}
+
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+    let low = c * 12.92;
+    let high = 1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, c <= vec3<f32>(0.0031308));
+}
+
+// Linear-light BT.2020 primaries to BT.709
+fn bt2020_to_bt709(c: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        dot(vec3<f32>(1.6605, -0.5876, -0.0728), c),
+        dot(vec3<f32>(-0.1246, 1.1329, -0.0083), c),
+        dot(vec3<f32>(-0.0182, -0.1006, 1.1187), c),
+    );
+}
+
+// SMPTE ST 2084 constants
+const PQ_M1: f32 = 0.1593017578125;
+const PQ_M2: f32 = 78.84375;
+const PQ_C1: f32 = 0.8359375;
+const PQ_C2: f32 = 18.8515625;
+const PQ_C3: f32 = 18.6875;
+
+// PQ signal to luminance in nits
+fn pq_to_nits(e: f32) -> f32 {
+    let p = pow(max(e, 0.0), 1.0 / PQ_M2);
+    return 10000.0 * pow(max(p - PQ_C1, 0.0) / (PQ_C2 - PQ_C3 * p), 1.0 / PQ_M1);
+}
+
+fn nits_to_pq(nits: f32) -> f32 {
+    let y = pow(max(nits, 0.0) / 10000.0, PQ_M1);
+    return pow((PQ_C1 + PQ_C2 * y) / (1.0 + PQ_C3 * y), PQ_M2);
+}
+
+// HLG signal to scene light, then the BT.2100 OOTF for a display peaking at source_peak_nits
+fn hlg_to_nits(e: vec3<f32>) -> vec3<f32> {
+    let a = 0.17883277;
+    let b = 0.28466892;
+    let c = 0.55991073;
+    let low = e * e / 3.0;
+    let high = (exp((e - c) / a) + b) / 12.0;
+    let scene = select(high, low, e <= vec3<f32>(0.5));
+    let peak = params.source_peak_nits;
+    let gamma = 1.2 + 0.42 * log2(peak / 1000.0) / log2(10.0);
+    let luma = max(dot(vec3<f32>(0.2627, 0.6780, 0.0593), scene), 1e-6);
+    return peak * pow(luma, gamma - 1.0) * scene;
+}
+
+fn hable_curve(x: f32) -> f32 {
+    let a = 0.15;
+    let b = 0.50;
+    let c = 0.10;
+    let d = 0.20;
+    let e = 0.02;
+    let f = 0.30;
+    return (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f;
+}
+
+// BT.2390 EETF: a Hermite knee in the PQ domain, returning nits
+fn bt2390(nits: f32, source_peak: f32, target_peak: f32) -> f32 {
+    let source_pq = nits_to_pq(source_peak);
+    let e1 = nits_to_pq(nits) / source_pq;
+    let max_lum = nits_to_pq(target_peak) / source_pq;
+    let ks = 1.5 * max_lum - 0.5;
+    var e2 = e1;
+    if e1 > ks {
+        let t = (e1 - ks) / (1.0 - ks);
+        let t2 = t * t;
+        let t3 = t2 * t;
+        e2 = (2.0 * t3 - 3.0 * t2 + 1.0) * ks + (t3 - 2.0 * t2 + t) * (1.0 - ks)
+            + (-2.0 * t3 + 3.0 * t2) * max_lum;
+    }
+    return pq_to_nits(min(e2, 1.0) * source_pq);
+}
+
+// Compresses a luminance in nits into 0..1 of the target peak
+fn tone_map(nits: f32) -> f32 {
+    let target_peak = params.target_peak_nits;
+    let source_peak = params.source_peak_nits;
+    let x = nits / target_peak;
+    if source_peak <= target_peak {
+        return x;
+    }
+    let w = source_peak / target_peak;
+    switch params.tone_map {
+        case 0u: {
+            return hable_curve(x) / hable_curve(w);
+        }
+        case 2u: {
+            return x * (1.0 + x / (w * w)) / (1.0 + x);
+        }
+        default: {
+            return bt2390(nits, source_peak, target_peak) / target_peak;
+        }
+    }
+}
+
+// Non-linear R'G'B' to linear BT.709 for the sRGB surface
+fn to_display(signal: vec3<f32>) -> vec3<f32> {
+    if params.transfer == 0u {
+        let linear = srgb_to_linear(signal);
+        if params.wide_gamut == 0u {
+            return linear;
+        }
+        return clamp(bt2020_to_bt709(linear), vec3<f32>(0.0), vec3<f32>(1.0));
+    }
+
+    var nits: vec3<f32>;
+    if params.transfer == 1u {
+        nits = vec3<f32>(pq_to_nits(signal.r), pq_to_nits(signal.g), pq_to_nits(signal.b));
+    } else {
+        nits = hlg_to_nits(signal);
+    }
+    if params.wide_gamut != 0u {
+        nits = max(bt2020_to_bt709(nits), vec3<f32>(0.0));
+    }
+    // Mapping the brightest channel keeps hues from shifting as highlights compress
+    let peak = max(nits.r, max(nits.g, nits.b));
+    if peak <= 0.0 {
+        return vec3<f32>(0.0);
+    }
+    return clamp(nits * (tone_map(peak) / peak), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// What shows through transparent video at framebuffer position `pos`
+fn background_at(pos: vec2<f32>) -> vec3<f32> {
+    if params.background == 1u {
+        let cell = vec2<u32>(pos / 8.0);
+        let light = ((cell.x + cell.y) & 1u) == 0u;
+        return srgb_to_linear(vec3<f32>(select(0.6, 0.8, light)));
+    }
+    return params.background_color.rgb;
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let uv = rotate_uv(in.tex_coords);
+    // Sampled unconditionally to keep texture sampling in uniform control flow
+    let base = textureSample(t_diffuse, s_diffuse, uv);
+    let plane1 = textureSample(t_plane1, s_diffuse, uv);
+    let plane2 = textureSample(t_plane2, s_diffuse, uv);
+
+    // Non-linear R'G'B' plus alpha
+    var signal: vec4<f32>;
+    switch params.plane_format {
+        case 1u: {
+            signal = vec4<f32>(yuv_to_rgb(vec3<f32>(base.r, plane1.r, plane2.r)), 1.0);
+        }
+        case 2u: {
+            signal = vec4<f32>(yuv_to_rgb(vec3<f32>(base.r, plane1.r, plane1.g)), 1.0);
+        }
+        case 3u: {
+            signal = base;
+        }
+        default: {
+            // The sRGB texture already decoded the samples; undo that to get the signal back
+            signal = vec4<f32>(linear_to_srgb(base.rgb), base.a);
+        }
+    }
+
+    let alpha = select(signal.a, 1.0, params.alpha_mode == 2u);
+    if params.alpha_only != 0u {
+        return vec4<f32>(srgb_to_linear(vec3<f32>(alpha)), 1.0);
+    }
+    var rgb = signal.rgb;
+    if params.alpha_mode == 1u {
+        rgb = select(vec3<f32>(0.0), rgb / alpha, alpha > 0.0);
+    }
+    let color = to_display(rgb);
+    if params.background == 0u {
+        return vec4<f32>(color, alpha);
+    }
+    return vec4<f32>(mix(background_at(in.clip_position.xy), color, alpha), 1.0);
 }
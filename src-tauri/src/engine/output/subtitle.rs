@@ -0,0 +1,232 @@
+use super::super::types::{Rect, SubtitleBitmap, SubtitleContent};
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+
+// Text line height relative to the video viewport, clamped to a legible pixel range
+const TEXT_HEIGHT_FRACTION: f32 = 0.055;
+const MIN_TEXT_PX: f32 = 14.0;
+const MAX_TEXT_PX: f32 = 96.0;
+// Distance of the text block from the bottom edge, and its maximum width (viewport fractions)
+const TEXT_BOTTOM_MARGIN: f32 = 0.05;
+const TEXT_MAX_WIDTH: f32 = 0.96;
+
+// Sans-serif faces shipped with each OS; the first one found is used for text cues
+const FONT_CANDIDATES: &[&str] = &[
+    "C:\\Windows\\Fonts\\segoeui.ttf",
+    "C:\\Windows\\Fonts\\arial.ttf",
+    "/System/Library/Fonts/Supplemental/Arial.ttf",
+    "/Library/Fonts/Arial.ttf",
+    "/System/Library/Fonts/Helvetica.ttc",
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationSans-Regular.ttf",
+    "/usr/share/fonts/noto/NotoSans-Regular.ttf",
+];
+
+/// A rasterised cue ready for upload. `placement` is relative to the video viewport
+/// (0..1 on both axes) so the overlay follows letterboxing and aspect changes.
+pub struct SubtitleOverlay {
+    pub rgba: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub placement: Rect,
+}
+
+/// Turns subtitle cues into RGBA overlays: text is drawn white with a black outline,
+/// bitmap rects are merged into one image positioned on their canvas.
+pub struct SubtitleRasterizer {
+    font: Option<FontVec>,
+}
+
+impl SubtitleRasterizer {
+    pub fn new() -> Self {
+        let font = FONT_CANDIDATES.iter().find_map(|path| {
+            let data = std::fs::read(path).ok()?;
+            let font = FontVec::try_from_vec_and_index(data, 0).ok()?;
+            log::info!("[Subtitles] Using font {}", path);
+            Some(font)
+        });
+        if font.is_none() {
+            log::warn!("[Subtitles] No system font found; text subtitles will not be shown");
+        }
+        Self { font }
+    }
+
+    /// `viewport` is the on-screen size of the video in pixels; text is rendered at that
+    /// resolution so it stays sharp.
+    pub fn rasterize(
+        &self,
+        content: &SubtitleContent,
+        viewport: (f32, f32),
+    ) -> Option<SubtitleOverlay> {
+        match content {
+            SubtitleContent::Text(text) => rasterize_text(self.font.as_ref()?, text, viewport),
+            SubtitleContent::Bitmap {
+                canvas_width,
+                canvas_height,
+                rects,
+            } => rasterize_bitmaps(*canvas_width, *canvas_height, rects),
+        }
+    }
+}
+
+fn rasterize_text(font: &FontVec, text: &str, viewport: (f32, f32)) -> Option<SubtitleOverlay> {
+    let lines: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+    if lines.is_empty() || viewport.0 < 1.0 || viewport.1 < 1.0 {
+        return None;
+    }
+
+    let px = (viewport.1 * TEXT_HEIGHT_FRACTION).clamp(MIN_TEXT_PX, MAX_TEXT_PX);
+    let scale = PxScale::from(px);
+    let scaled = font.as_scaled(scale);
+    let border = (px / 14.0).ceil().max(1.0) as i32;
+    let line_height = scaled.height() + scaled.line_gap();
+
+    // Lay out each line and measure its advance width
+    let mut laid_out = Vec::with_capacity(lines.len());
+    let mut max_width: f32 = 0.0;
+    for line in &lines {
+        let mut caret = 0.0;
+        let mut previous = None;
+        let mut glyphs = Vec::new();
+        for c in line.chars() {
+            let id = scaled.glyph_id(c);
+            if let Some(prev) = previous {
+                caret += scaled.kern(prev, id);
+            }
+            glyphs.push((id, caret));
+            caret += scaled.h_advance(id);
+            previous = Some(id);
+        }
+        max_width = max_width.max(caret);
+        laid_out.push((glyphs, caret));
+    }
+
+    let pad = border as f32 + 1.0;
+    let width = (max_width + pad * 2.0).ceil() as u32;
+    let height = (line_height * lines.len() as f32 + pad * 2.0).ceil() as u32;
+
+    // Glyph coverage, centred line by line
+    let mut coverage = vec![0.0f32; (width * height) as usize];
+    for (row, (glyphs, line_width)) in laid_out.iter().enumerate() {
+        let x0 = pad + (max_width - line_width) / 2.0;
+        let baseline = pad + scaled.ascent() + line_height * row as f32;
+        for (id, x) in glyphs {
+            let glyph = id.with_scale_and_position(scale, point(x0 + x, baseline));
+            if let Some(outlined) = font.outline_glyph(glyph) {
+                let bounds = outlined.px_bounds();
+                outlined.draw(|gx, gy, c| {
+                    let px_x = bounds.min.x as i32 + gx as i32;
+                    let px_y = bounds.min.y as i32 + gy as i32;
+                    if px_x >= 0 && px_y >= 0 && (px_x as u32) < width && (px_y as u32) < height {
+                        let i = (px_y as u32 * width + px_x as u32) as usize;
+                        coverage[i] = (coverage[i] + c).min(1.0);
+                    }
+                });
+            }
+        }
+    }
+
+    // White fill over a black outline made by dilating the coverage
+    let mut rgba = vec![0u8; (width * height * 4) as usize];
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let mut edge: f32 = 0.0;
+            for dy in -border..=border {
+                for dx in -border..=border {
+                    let (sx, sy) = (x + dx, y + dy);
+                    if dx * dx + dy * dy > border * border
+                        || sx < 0
+                        || sy < 0
+                        || sx >= width as i32
+                        || sy >= height as i32
+                    {
+                        continue;
+                    }
+                    edge = edge.max(coverage[(sy as u32 * width + sx as u32) as usize]);
+                }
+            }
+            let fill = coverage[(y as u32 * width + x as u32) as usize];
+            let alpha = fill + edge * (1.0 - fill);
+            if alpha <= 0.0 {
+                continue;
+            }
+            let value = (fill / alpha * 255.0).round() as u8;
+            let i = ((y as u32 * width + x as u32) * 4) as usize;
+            rgba[i..i + 4].copy_from_slice(&[value, value, value, (alpha * 255.0).round() as u8]);
+        }
+    }
+
+    let mut w = width as f32 / viewport.0;
+    let mut h = height as f32 / viewport.1;
+    if w > TEXT_MAX_WIDTH {
+        h *= TEXT_MAX_WIDTH / w;
+        w = TEXT_MAX_WIDTH;
+    }
+
+    Some(SubtitleOverlay {
+        rgba,
+        width,
+        height,
+        placement: Rect {
+            x: (1.0 - w) / 2.0,
+            y: (1.0 - TEXT_BOTTOM_MARGIN - h).max(0.0),
+            width: w,
+            height: h,
+        },
+    })
+}
+
+fn rasterize_bitmaps(
+    canvas_width: u32,
+    canvas_height: u32,
+    rects: &[SubtitleBitmap],
+) -> Option<SubtitleOverlay> {
+    // Merge every rect into one image covering their bounding box
+    let min_x = rects.iter().map(|r| r.x).min()?;
+    let min_y = rects.iter().map(|r| r.y).min()?;
+    let max_x = rects.iter().map(|r| r.x + r.width).max()?;
+    let max_y = rects.iter().map(|r| r.y + r.height).max()?;
+    let (width, height) = (max_x - min_x, max_y - min_y);
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    let mut rgba = vec![0u8; (width * height * 4) as usize];
+    for rect in rects {
+        let row_bytes = (rect.width * 4) as usize;
+        for y in 0..rect.height {
+            let src = (y * rect.width * 4) as usize;
+            let dst = (((rect.y - min_y + y) * width + (rect.x - min_x)) * 4) as usize;
+            rgba[dst..dst + row_bytes].copy_from_slice(&rect.rgba[src..src + row_bytes]);
+        }
+    }
+
+    // Rects are authored for the canvas; without one, treat the bounding box as the canvas
+    let canvas_width = if canvas_width == 0 {
+        max_x
+    } else {
+        canvas_width
+    } as f32;
+    let canvas_height = if canvas_height == 0 {
+        max_y
+    } else {
+        canvas_height
+    } as f32;
+
+    Some(SubtitleOverlay {
+        rgba,
+        width,
+        height,
+        placement: Rect {
+            x: min_x as f32 / canvas_width,
+            y: min_y as f32 / canvas_height,
+            width: width as f32 / canvas_width,
+            height: height as f32 / canvas_height,
+        },
+    })
+}
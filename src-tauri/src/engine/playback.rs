@@ -1,9 +1,20 @@
+use super::media::{ArchiveEntry, ImageSequence, MediaInput, SubtitleFile, SubtitleTimeline};
+use super::output::Renderer;
 use super::state::PreviewState;
-use super::types::SyncMode;
+use super::types::{
+    AnimationLoop, Chapter, ChapterStep, ColorInfo, SeekMode, SubtitleSelection, SyncMode,
+};
 use std::path::PathBuf;
 use std::time::{Duration, Instant};
 use tauri::{Emitter, Window};
 
+// Tolerance for a seek landing just short of a chapter start
+const CHAPTER_START_TOLERANCE_SECS: f64 = 0.05;
+// Further than this into a chapter, "previous" restarts it instead of going back one
+const PREVIOUS_CHAPTER_GRACE_SECS: f64 = 3.0;
+// How often a followed file is checked for new data once playback has caught up
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
 pub struct PlaybackEngine {
     state: PreviewState,
     window: Window,
@@ -21,7 +32,26 @@ impl PlaybackEngine {
         let audio_producer_clone = self.state.audio_producer.clone();
         let seek_target_clone = self.state.seek_target.clone();
         let sync_mode_clone = self.state.sync_mode.clone();
+        let seek_mode_clone = self.state.seek_mode.clone();
+        let downmix_mode_clone = self.state.downmix_mode.clone();
+        let resample_quality_clone = self.state.resample_quality.clone();
+        let deinterlace_mode_clone = self.state.deinterlace_mode.clone();
+        let video_filter_clone = self.state.video_filter.clone();
+        let media_streams_clone = self.state.media_streams.clone();
+        let video_stream_request_clone = self.state.video_stream_request.clone();
+        let audio_stream_request_clone = self.state.audio_stream_request.clone();
+        let subtitle_request_clone = self.state.subtitle_request.clone();
+        let subtitle_file_clone = self.state.subtitle_file.clone();
+        let subtitle_offset_clone = self.state.subtitle_offset.clone();
+        let chapters_clone = self.state.chapters.clone();
+        let chapter_request_clone = self.state.chapter_request.clone();
+        let image_sequence_clone = self.state.image_sequence.clone();
+        let animation_loop_clone = self.state.animation_loop.clone();
+        let follow_mode_clone = self.state.follow_mode.clone();
+        let follow_idle_timeout_clone = self.state.follow_idle_timeout.clone();
+        let sequence_frame_rate = *self.state.sequence_frame_rate.lock().unwrap();
         let quality_mode = *self.state.quality_mode.lock().unwrap();
+        let audio_format = self.state.audio_format.lock().unwrap().clone();
         let window = self.window.clone();
 
         // Capture the current session ID to ensure we don't run old threads
@@ -31,7 +61,39 @@ impl PlaybackEngine {
         };
 
         std::thread::spawn(move || {
-            let mut decoder = match crate::engine::media::Decoder::new(&path, quality_mode) {
+            let downmix_mode = *downmix_mode_clone.lock().unwrap();
+            let resample_quality = *resample_quality_clone.lock().unwrap();
+            let deinterlace_mode = *deinterlace_mode_clone.lock().unwrap();
+            let high_bit_depth = renderer_clone
+                .lock()
+                .unwrap()
+                .as_ref()
+                .is_some_and(|r| r.high_bit_depth);
+            let sequence = ImageSequence::detect(&path, sequence_frame_rate);
+            let archive_entry = ArchiveEntry::parse(&path);
+            let input = match (&archive_entry, &sequence) {
+                (Some(entry), _) => match entry.open() {
+                    Ok(source) => MediaInput::Source {
+                        name: &entry.name,
+                        source,
+                    },
+                    Err(e) => {
+                        eprintln!("[PlaybackEngine] Archive error: {}", e);
+                        return;
+                    }
+                },
+                (None, Some(sequence)) => MediaInput::Sequence(sequence),
+                (None, None) => MediaInput::File(&path),
+            };
+            let mut decoder = match crate::engine::media::Decoder::new(
+                input,
+                quality_mode,
+                &audio_format,
+                downmix_mode,
+                resample_quality,
+                deinterlace_mode,
+                high_bit_depth,
+            ) {
                 Ok(d) => d,
                 Err(e) => {
                     eprintln!("[PlaybackEngine] Decoder error: {}", e);
@@ -39,17 +101,42 @@ impl PlaybackEngine {
                 }
             };
 
-            if decoder.video_stream_index.is_none() {
+            // Only plain files on disk can be watched for growth
+            let followable =
+                archive_entry.is_none() && sequence.is_none() && decoder.animation().is_none();
+            *media_streams_clone.lock().unwrap() = decoder.streams();
+            let chapters = decoder.chapters();
+            *chapters_clone.lock().unwrap() = chapters.clone();
+            if let Some(sequence) = &sequence {
+                let info = sequence.info();
+                if !info.missing.is_empty() {
+                    log::warn!(
+                        "[PlaybackEngine] Sequence {} is missing frames {:?}",
+                        info.pattern,
+                        info.missing
+                    );
+                }
+                let _ = window.emit("sequence-opened", info);
+            }
+            *image_sequence_clone.lock().unwrap() = sequence;
+            if let Some(animation) = decoder.animation() {
+                let _ = window.emit("animation-opened", animation.clone());
+            }
+
+            {
                 let mut guard = renderer_clone.lock().unwrap();
                 if let Some(r) = guard.as_mut() {
-                    r.clear_video();
-                    let _ = r.repaint();
+                    r.set_subtitle(None);
+                    if decoder.video_stream_index.is_none() {
+                        r.clear_video();
+                        let _ = r.repaint();
+                    }
                 }
             }
 
             let mut current_time = 0.0;
             let mut reference_start_time: Option<Instant> = None;
-            let (duration, _, _) = decoder.get_metadata();
+            let (mut duration, _, _) = decoder.get_metadata();
 
             // Initial update so UI knows duration immediately
             let _ = window.emit(
@@ -62,6 +149,23 @@ impl PlaybackEngine {
             );
 
             let mut iteration_count = 0;
+            // After a seek, keep decoding (even while paused) until the target frame is shown
+            let mut awaiting_seek_frame = false;
+            // Cues of the active subtitle track (or loaded file) and the one currently on screen
+            let mut subtitles = SubtitleTimeline::default();
+            let mut subtitle_file: Option<std::sync::Arc<SubtitleFile>> = None;
+            let mut shown_cue: Option<u64> = None;
+            let mut current_chapter: Option<usize> = None;
+            // Size of a followed file when last checked, and when it last changed
+            let mut followed_size = 0;
+            let mut last_growth = Instant::now();
+            let mut last_follow_poll = Instant::now();
+            // Completed passes through an animated image
+            let mut loops_played = 0;
+            let mut current_colorspace: Option<ColorInfo> = None;
+            // Filter chain the decoder is currently running
+            let mut applied_filter = video_filter_clone.lock().unwrap().clone();
+            decoder.set_video_filter(applied_filter.as_deref());
 
             loop {
                 // Check if session has changed (user opened new file)
@@ -69,6 +173,88 @@ impl PlaybackEngine {
                     break;
                 }
 
+                // Check for track switches; the new decoder resumes via a seek to the current time
+                let video_request = video_stream_request_clone.lock().unwrap().take();
+                let audio_request = audio_stream_request_clone.lock().unwrap().take();
+                if video_request.is_some() || audio_request.is_some() {
+                    if let Some(index) = video_request {
+                        if let Err(e) = decoder.select_video_stream(index) {
+                            log::error!("[PlaybackEngine] Video stream switch failed: {}", e);
+                        }
+                    }
+                    if let Some(index) = audio_request {
+                        if let Err(e) = decoder.select_audio_stream(index) {
+                            log::error!("[PlaybackEngine] Audio stream switch failed: {}", e);
+                        }
+                    }
+                    *media_streams_clone.lock().unwrap() = decoder.streams();
+                    seek_target_clone
+                        .lock()
+                        .unwrap()
+                        .get_or_insert(current_time);
+                }
+
+                // Positive offsets delay subtitles relative to the picture
+                let subtitle_offset = *subtitle_offset_clone.lock().unwrap();
+                let subtitle_request = subtitle_request_clone.lock().unwrap().take();
+                if let Some(selection) = subtitle_request {
+                    let index = match selection {
+                        SubtitleSelection::Track(index) => Some(index),
+                        SubtitleSelection::File | SubtitleSelection::Off => None,
+                    };
+                    if let Err(e) = decoder.select_subtitle_stream(index) {
+                        log::error!("[PlaybackEngine] Subtitle switch failed: {}", e);
+                    }
+                    *media_streams_clone.lock().unwrap() = decoder.streams();
+                    subtitles.clear();
+                    subtitle_file = match selection {
+                        SubtitleSelection::File => subtitle_file_clone.lock().unwrap().clone(),
+                        _ => None,
+                    };
+                    shown_cue = None;
+                    if let Some(r) = renderer_clone.lock().unwrap().as_mut() {
+                        r.set_subtitle(None);
+                        update_subtitle(
+                            r,
+                            &mut subtitles,
+                            subtitle_file.as_deref(),
+                            &mut shown_cue,
+                            current_time - subtitle_offset,
+                        );
+                        let _ = r.repaint();
+                    }
+                    if index.is_some() {
+                        // Re-read from the current position to pick up the cue on screen
+                        seek_target_clone
+                            .lock()
+                            .unwrap()
+                            .get_or_insert(current_time);
+                    }
+                }
+
+                // A new filter chain re-decodes from the current position so it shows at once
+                {
+                    let filter = video_filter_clone.lock().unwrap();
+                    if *filter != applied_filter {
+                        applied_filter = filter.clone();
+                        decoder.set_video_filter(applied_filter.as_deref());
+                        if decoder.video_stream_index.is_some() {
+                            seek_target_clone
+                                .lock()
+                                .unwrap()
+                                .get_or_insert(current_time);
+                        }
+                    }
+                }
+
+                // Chapter navigation goes through the regular seek below
+                let chapter_request = chapter_request_clone.lock().unwrap().take();
+                if let Some(step) = chapter_request {
+                    if let Some(target) = chapter_target(&chapters, current_time, step) {
+                        *seek_target_clone.lock().unwrap() = Some(target);
+                    }
+                }
+
                 // Check for Seek Request
                 let mut seek_opt = None;
                 {
@@ -80,11 +266,16 @@ impl PlaybackEngine {
                 }
 
                 if let Some(target) = seek_opt {
-                    log::info!("[PlaybackEngine] Seeking to {}s", target);
-                    if let Err(e) = decoder.seek(target) {
+                    let mode = *seek_mode_clone.lock().unwrap();
+                    log::info!("[PlaybackEngine] Seeking to {}s ({:?})", target, mode);
+                    if let Err(e) = decoder.seek(target, mode) {
                         log::error!("[PlaybackEngine] Seek failed: {}", e);
                     } else {
+                        // Provisional until the first frame arrives; the next video result
+                        // replaces it with the real PTS of the frame on screen.
                         current_time = target;
+                        awaiting_seek_frame = decoder.video_stream_index.is_some();
+                        subtitles.clear();
                         reference_start_time = None; // Reset clock on seek
                                                      // Send immediate update
                         let _ = window.emit(
@@ -98,10 +289,78 @@ impl PlaybackEngine {
                     }
                 }
 
+                // Apply audio setting changes mid-playback (cheap no-ops when unchanged)
+                decoder.set_downmix_mode(*downmix_mode_clone.lock().unwrap());
+                decoder.set_resample_quality(*resample_quality_clone.lock().unwrap());
+                decoder.set_deinterlace_mode(*deinterlace_mode_clone.lock().unwrap());
+                let following = followable && *follow_mode_clone.lock().unwrap();
+                decoder.set_follow(following);
+                // A followed file's duration tracks its growth, wherever the playhead is
+                if following && last_follow_poll.elapsed() >= FOLLOW_POLL_INTERVAL {
+                    last_follow_poll = Instant::now();
+                    duration = decoder.estimate_duration();
+                }
+
                 let decode_result = decoder.decode_next();
                 let result = match decode_result {
                     Ok(Some(r)) => r,
                     Ok(None) => {
+                        // A growing file is waited on until it stops growing for the timeout
+                        if followable && *follow_mode_clone.lock().unwrap() {
+                            let size = std::fs::metadata(&path).map_or(0, |m| m.len());
+                            if size != followed_size {
+                                followed_size = size;
+                                last_growth = Instant::now();
+                            }
+                            let idle_timeout = *follow_idle_timeout_clone.lock().unwrap();
+                            if last_growth.elapsed().as_secs_f64() < idle_timeout {
+                                duration = decoder.estimate_duration().max(current_time);
+                                // New frames are timed afresh rather than rushed to catch up
+                                reference_start_time = None;
+                                let _ = window.emit(
+                                    "playback-update",
+                                    crate::engine::PlaybackPayload {
+                                        current_time,
+                                        duration,
+                                        status: crate::engine::PlaybackStatus::Buffering,
+                                    },
+                                );
+                                std::thread::sleep(FOLLOW_POLL_INTERVAL);
+                                continue;
+                            }
+                            log::info!(
+                                "[PlaybackEngine] {:?} idle for {}s, leaving follow mode",
+                                path,
+                                idle_timeout
+                            );
+                            *follow_mode_clone.lock().unwrap() = false;
+                            let _ = window.emit("follow-mode-ended", ());
+                            // Drain what the decoder still holds before finishing
+                            decoder.set_follow(false);
+                            continue;
+                        }
+                        // Animated images restart from the top while the loop mode allows
+                        if let Some(animation) = decoder.animation() {
+                            loops_played += 1;
+                            let keep_looping = match *animation_loop_clone.lock().unwrap() {
+                                AnimationLoop::Forever => true,
+                                AnimationLoop::FileCount => {
+                                    animation.loop_count == 0 || loops_played < animation.loop_count
+                                }
+                                AnimationLoop::Once => false,
+                            };
+                            if keep_looping {
+                                match decoder.seek(0.0, SeekMode::Keyframe) {
+                                    Ok(()) => {
+                                        reference_start_time = None;
+                                        continue;
+                                    }
+                                    Err(e) => {
+                                        log::error!("[PlaybackEngine] Loop restart failed: {}", e)
+                                    }
+                                }
+                            }
+                        }
                         let _ = window.emit(
                             "playback-update",
                             crate::engine::PlaybackPayload {
@@ -139,25 +398,47 @@ impl PlaybackEngine {
 
                 match result {
                     crate::engine::media::DecodeResult::Video {
-                        data,
-                        width,
-                        height,
-                        stride,
+                        frame,
                         pts,
+                        rotation,
+                        pixel_aspect,
                     } => {
                         current_time = pts;
                         should_emit_update = true; // Always emit on video frame
+                        awaiting_seek_frame = false;
+
+                        // The filter was validated against a stand-in frame; tell the UI
+                        // when it doesn't fit the real ones
+                        if let Some(failure) = decoder.take_filter_failure() {
+                            let _ = window.emit("video-filter-failed", failure);
+                        }
+
+                        if decoder.colorspace() != current_colorspace.as_ref() {
+                            current_colorspace = decoder.colorspace().cloned();
+                            let _ = window.emit("colorspace-changed", current_colorspace.clone());
+                        }
 
                         let mut guard = renderer_clone.lock().unwrap();
                         if let Some(r) = guard.as_mut() {
-                            let _ = r.render_frame(&data, width, height, stride);
+                            r.set_orientation(rotation, pixel_aspect);
+                            update_subtitle(
+                                r,
+                                &mut subtitles,
+                                subtitle_file.as_deref(),
+                                &mut shown_cue,
+                                current_time - subtitle_offset,
+                            );
+                            let _ = r.render_frame(&frame);
                         }
 
                         // Dynamic Pacing
                         let mode = *sync_mode_clone.lock().unwrap();
                         match mode {
                             SyncMode::Fixed => {
-                                std::thread::sleep(Duration::from_millis(30));
+                                // Bob emits one frame per field; keep the source's pace
+                                std::thread::sleep(
+                                    Duration::from_millis(30) / decoder.video_rate_factor(),
+                                );
                             }
                             SyncMode::Realtime => {
                                 if reference_start_time.is_none() {
@@ -183,6 +464,18 @@ impl PlaybackEngine {
                             current_time = pts;
                             should_emit_update = true;
 
+                            if let Some(r) = renderer_clone.lock().unwrap().as_mut() {
+                                if update_subtitle(
+                                    r,
+                                    &mut subtitles,
+                                    subtitle_file.as_deref(),
+                                    &mut shown_cue,
+                                    current_time - subtitle_offset,
+                                ) {
+                                    let _ = r.repaint();
+                                }
+                            }
+
                             // Dynamic Pacing for Audio-Only Mode
                             // We must sleep to keep the loop from racing ahead of real-time
                             let mode = *sync_mode_clone.lock().unwrap();
@@ -215,9 +508,14 @@ impl PlaybackEngine {
                             std::thread::yield_now();
                         }
                     }
+                    crate::engine::media::DecodeResult::Subtitle(cue) => {
+                        subtitles.push(cue);
+                    }
                 }
 
-                if !decoder.audio_buffer.is_empty() {
+                // Hold decoded audio while paused (e.g. after a paused seek) so it doesn't blip out
+                let is_playing = *playing_clone.lock().unwrap();
+                if is_playing && !decoder.audio_buffer.is_empty() {
                     let samples_to_push = decoder.audio_buffer.len();
                     if let Ok(mut guard) = audio_producer_clone.lock() {
                         if let Some(ref mut producer) = *guard {
@@ -231,16 +529,27 @@ impl PlaybackEngine {
                                 );
                             }
 
-                            // If buffer is very full, slow down slightly
-                            if producer.len() > 24000 {
-                                // ~0.5s of audio
+                            // If buffer is very full (~0.25s of audio), slow down slightly
+                            let high_water = audio_format.sample_rate as usize
+                                * audio_format.channels as usize
+                                / 4;
+                            if producer.len() > high_water {
                                 std::thread::sleep(std::time::Duration::from_millis(5));
                             }
                         }
                     }
                 }
 
-                if !*playing_clone.lock().unwrap() {
+                let chapter = chapter_at(&chapters, current_time);
+                if chapter != current_chapter {
+                    current_chapter = chapter;
+                    let _ = window.emit(
+                        "chapter-changed",
+                        chapter.map(|index| chapters[index].clone()),
+                    );
+                }
+
+                if !is_playing && !awaiting_seek_frame {
                     let _ = window.emit(
                         "playback-update",
                         crate::engine::PlaybackPayload {
@@ -254,6 +563,34 @@ impl PlaybackEngine {
                         if *session_id_clone.lock().unwrap() != current_session {
                             return;
                         }
+                        // Wake up to decode and show the exact frame for a paused seek
+                        if seek_target_clone.lock().unwrap().is_some()
+                            || video_stream_request_clone.lock().unwrap().is_some()
+                            || audio_stream_request_clone.lock().unwrap().is_some()
+                            || subtitle_request_clone.lock().unwrap().is_some()
+                            || chapter_request_clone.lock().unwrap().is_some()
+                            || *video_filter_clone.lock().unwrap() != applied_filter
+                        {
+                            break;
+                        }
+                        if followable
+                            && *follow_mode_clone.lock().unwrap()
+                            && last_follow_poll.elapsed() >= FOLLOW_POLL_INTERVAL
+                        {
+                            last_follow_poll = Instant::now();
+                            let estimate = decoder.estimate_duration();
+                            if estimate != duration {
+                                duration = estimate;
+                                let _ = window.emit(
+                                    "playback-update",
+                                    crate::engine::PlaybackPayload {
+                                        current_time,
+                                        duration,
+                                        status: crate::engine::PlaybackStatus::Paused,
+                                    },
+                                );
+                            }
+                        }
                         std::thread::sleep(std::time::Duration::from_millis(100));
                     }
                     reference_start_time = None; // Reset clock on resume
@@ -278,3 +615,51 @@ impl PlaybackEngine {
         });
     }
 }
+
+/// Points the renderer's overlay at the cue showing at `time`, taken from the loaded
+/// subtitle file if there is one. Returns true when the overlay changed.
+fn update_subtitle(
+    renderer: &mut Renderer,
+    subtitles: &mut SubtitleTimeline,
+    subtitle_file: Option<&SubtitleFile>,
+    shown_cue: &mut Option<u64>,
+    time: f64,
+) -> bool {
+    let active = match subtitle_file {
+        Some(file) => file.active_at(time),
+        None => subtitles.active_at(time),
+    };
+    let active_id = active.as_ref().map(|(id, _)| *id);
+    if active_id == *shown_cue {
+        return false;
+    }
+    *shown_cue = active_id;
+    renderer.set_subtitle(active.map(|(_, content)| content.clone()));
+    true
+}
+
+/// Index of the chapter covering `time`, if any.
+fn chapter_at(chapters: &[Chapter], time: f64) -> Option<usize> {
+    chapters
+        .iter()
+        .rposition(|c| c.start <= time && time < c.end)
+}
+
+/// Start time of the chapter `step` moves to from `time`.
+fn chapter_target(chapters: &[Chapter], time: f64, step: ChapterStep) -> Option<f64> {
+    // The last chapter that has started, so gaps between chapters count as the earlier one
+    let current = chapters
+        .iter()
+        .rposition(|c| c.start <= time + CHAPTER_START_TOLERANCE_SECS);
+    match step {
+        ChapterStep::Next => chapters.get(current.map_or(0, |i| i + 1)).map(|c| c.start),
+        ChapterStep::Previous => {
+            let i = current?;
+            if i == 0 || time - chapters[i].start > PREVIOUS_CHAPTER_GRACE_SECS {
+                Some(chapters[i].start)
+            } else {
+                Some(chapters[i - 1].start)
+            }
+        }
+    }
+}
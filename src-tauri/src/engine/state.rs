@@ -1,6 +1,9 @@
 use super::types::*;
 use std::sync::{Arc, Mutex};
 
+// Seconds without growth before follow mode switches itself off, until set otherwise
+pub const DEFAULT_FOLLOW_IDLE_TIMEOUT: f64 = 10.0;
+
 #[derive(Clone)]
 pub struct PreviewState {
     pub renderer: Arc<Mutex<Option<crate::engine::output::Renderer>>>,
@@ -8,7 +11,28 @@ pub struct PreviewState {
     pub is_playing: Arc<Mutex<bool>>,
     pub session_id: Arc<Mutex<u64>>,
     pub audio_producer: Arc<Mutex<Option<ringbuf::HeapProducer<f32>>>>,
+    pub audio_format: Arc<Mutex<AudioOutputFormat>>,
     pub volume: Arc<std::sync::atomic::AtomicU32>,
+    pub downmix_mode: Arc<Mutex<DownmixMode>>,
+    pub resample_quality: Arc<Mutex<ResampleQuality>>,
+    pub deinterlace_mode: Arc<Mutex<DeinterlaceMode>>,
+    pub video_filter: Arc<Mutex<Option<String>>>,
     pub seek_target: Arc<Mutex<Option<f64>>>,
     pub sync_mode: Arc<Mutex<SyncMode>>,
+    pub seek_mode: Arc<Mutex<SeekMode>>,
+    pub media_streams: Arc<Mutex<Vec<StreamInfo>>>,
+    pub video_stream_request: Arc<Mutex<Option<usize>>>,
+    pub audio_stream_request: Arc<Mutex<Option<usize>>>,
+    pub subtitle_request: Arc<Mutex<Option<SubtitleSelection>>>,
+    pub subtitle_file: Arc<Mutex<Option<Arc<crate::engine::media::SubtitleFile>>>>,
+    pub subtitle_offset: Arc<Mutex<f64>>,
+    pub chapters: Arc<Mutex<Vec<Chapter>>>,
+    pub chapter_request: Arc<Mutex<Option<ChapterStep>>>,
+    // Frame rate numbered image sequences play at, and the one open (if any)
+    pub sequence_frame_rate: Arc<Mutex<f64>>,
+    pub image_sequence: Arc<Mutex<Option<crate::engine::media::ImageSequence>>>,
+    pub animation_loop: Arc<Mutex<AnimationLoop>>,
+    // Keep reading files that are still growing, until idle for the timeout (seconds)
+    pub follow_mode: Arc<Mutex<bool>>,
+    pub follow_idle_timeout: Arc<Mutex<f64>>,
 }
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum DownmixMode {
+    ItuBs775,   // ITU-R BS.775: centre and surrounds at -3 dB
+    LoRo,       // Left-only/Right-only: centre at -3 dB, surrounds at -6 dB
+    LtRt,       // Left-total/Right-total: matrix-encoded surround (Dolby Surround compatible)
+    CenterOnly, // Dialog check: centre channel alone on both speakers
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum ResampleQuality {
+    Fast,   // Short filter, coarse phases: lowest CPU
+    Medium, // swresample defaults
+    Best,   // Long filter, fine phases, cutoff close to Nyquist
+}
+
+/// The stream configuration negotiated with the output device. The decoder renders
+/// interleaved f32 at this rate and channel count; the device callback converts to
+/// `sample_format` (e.g. "i16", "u16") on the way out.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AudioOutputFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub sample_format: String,
+}
+
+impl Default for AudioOutputFormat {
+    // Used when no output device could be opened
+    fn default() -> Self {
+        Self {
+            sample_rate: 48000,
+            channels: 2,
+            sample_format: "f32".to_string(),
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use serde::Serialize;
+
+/// How a decoded frame's planes are laid out for upload to the GPU.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlaneLayout {
+    Rgba,      // One packed RGBA plane, converted on the CPU (fallback)
+    Rgba16,    // As `Rgba` with 16 bits per component, for high bit depth sources
+    Yuv420p,   // Y, U and V planes, chroma at half width and height
+    Yuv422p,   // Y, U and V planes, chroma at half width
+    Nv12,      // Y plane plus interleaved UV at half width and height
+    Yuv420p16, // As `Yuv420p` with 16-bit samples (10/12-bit values in the low bits)
+    Yuv422p16, // As `Yuv422p` with 16-bit samples
+    P010,      // As `Nv12` with 16-bit samples, values in the high bits (P010/P016)
+}
+
+impl PlaneLayout {
+    pub fn plane_count(self) -> usize {
+        match self {
+            PlaneLayout::Rgba | PlaneLayout::Rgba16 => 1,
+            PlaneLayout::Yuv420p
+            | PlaneLayout::Yuv422p
+            | PlaneLayout::Yuv420p16
+            | PlaneLayout::Yuv422p16 => 3,
+            PlaneLayout::Nv12 | PlaneLayout::P010 => 2,
+        }
+    }
+
+    /// Storage size of one sample in bits: 8 or 16.
+    pub fn container_bits(self) -> u32 {
+        match self {
+            PlaneLayout::Rgba | PlaneLayout::Yuv420p | PlaneLayout::Yuv422p | PlaneLayout::Nv12 => {
+                8
+            }
+            PlaneLayout::Rgba16
+            | PlaneLayout::Yuv420p16
+            | PlaneLayout::Yuv422p16
+            | PlaneLayout::P010 => 16,
+        }
+    }
+
+    /// Size of plane `index` for a `width`x`height` frame.
+    pub fn plane_size(self, index: usize, width: u32, height: u32) -> (u32, u32) {
+        if index == 0 {
+            return (width, height);
+        }
+        match self {
+            PlaneLayout::Rgba | PlaneLayout::Rgba16 => (width, height),
+            PlaneLayout::Yuv420p
+            | PlaneLayout::Nv12
+            | PlaneLayout::Yuv420p16
+            | PlaneLayout::P010 => (width.div_ceil(2), height.div_ceil(2)),
+            PlaneLayout::Yuv422p | PlaneLayout::Yuv422p16 => (width.div_ceil(2), height),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum ColorMatrix {
+    Rgb,       // Not YUV: samples are already R'G'B'
+    Bt601,     // SD (BT.470BG / SMPTE 170M)
+    Bt709,     // HD
+    Bt2020,    // UHD, non-constant luminance
+    Smpte240m, // Early HD
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum ColorRange {
+    Limited, // 16-235 luma, 16-240 chroma (broadcast)
+    Full,    // 0-255 (JPEG)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum TransferFunction {
+    Sdr, // Gamma-style SDR curves (BT.709, sRGB, ...)
+    Pq,  // SMPTE ST 2084 (HDR10)
+    Hlg, // ARIB STD-B67 hybrid log-gamma
+}
+
+/// Static HDR metadata in nits: mastering display luminance and content light levels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct HdrMetadata {
+    pub mastering_max_nits: Option<f32>,
+    pub mastering_min_nits: Option<f32>,
+    pub max_cll: Option<f32>,
+    pub max_fall: Option<f32>,
+}
+
+impl HdrMetadata {
+    /// Fields set in `self` win; the rest come from `fallback`.
+    pub fn or(self, fallback: HdrMetadata) -> HdrMetadata {
+        HdrMetadata {
+            mastering_max_nits: self.mastering_max_nits.or(fallback.mastering_max_nits),
+            mastering_min_nits: self.mastering_min_nits.or(fallback.mastering_min_nits),
+            max_cll: self.max_cll.or(fallback.max_cll),
+            max_fall: self.max_fall.or(fallback.max_fall),
+        }
+    }
+}
+
+/// How the decoder is interpreting the colours of the video on screen.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ColorInfo {
+    pub matrix: ColorMatrix,
+    // The stream didn't flag its matrix, so one was picked from the frame size
+    pub matrix_guessed: bool,
+    pub range: ColorRange,
+    // ffmpeg names, e.g. "bt709" or "smpte2084"; `None` when unspecified
+    pub primaries: Option<String>,
+    pub transfer: Option<String>,
+    // Present for PQ and HLG video
+    pub hdr: Option<HdrMetadata>,
+}
+
+pub struct FramePlane {
+    pub data: Vec<u8>,
+    // Bytes per row, including any padding the decoder left
+    pub stride: u32,
+}
+
+/// A decoded picture ready for the renderer.
+pub struct VideoFrame {
+    pub layout: PlaneLayout,
+    pub width: u32,
+    pub height: u32,
+    pub planes: Vec<FramePlane>,
+    // Significant bits per sample, at most `layout.container_bits()`
+    pub bit_depth: u32,
+    // The RGBA planes carry transparency; other frames are opaque
+    pub has_alpha: bool,
+    pub matrix: ColorMatrix,
+    pub range: ColorRange,
+    pub transfer: TransferFunction,
+    // BT.2020 primaries, converted to BT.709 for display
+    pub wide_gamut: bool,
+    pub hdr: HdrMetadata,
+}
@@ -0,0 +1,147 @@
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum StreamKind {
+    Video,
+    Audio,
+    Subtitle,
+    Data,
+    Attachment,
+    Unknown,
+}
+
+/// One stream of the open container, as reported to the UI for track selection.
+#[derive(Clone, Debug, Serialize)]
+pub struct StreamInfo {
+    pub index: usize,
+    pub kind: StreamKind,
+    pub codec: String,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    // Audio
+    pub channels: Option<u16>,
+    pub channel_layout: Option<String>,
+    pub sample_rate: Option<u32>,
+    // Video
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub is_default: bool,
+    // Currently being decoded
+    pub active: bool,
+}
+
+/// A pending change of subtitle track.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SubtitleSelection {
+    Track(usize),
+    // The sidecar file held in `PreviewState::subtitle_file`
+    File,
+    Off,
+}
+
+/// A paletted subtitle rect expanded to RGBA (straight alpha), positioned on the
+/// subtitle canvas.
+#[derive(Clone, Debug)]
+pub struct SubtitleBitmap {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub enum SubtitleContent {
+    // Plain text, lines separated by '\n'
+    Text(String),
+    // PGS/DVB/VobSub pictures; the canvas is the resolution the rects are authored for
+    Bitmap {
+        canvas_width: u32,
+        canvas_height: u32,
+        rects: Vec<SubtitleBitmap>,
+    },
+}
+
+impl SubtitleContent {
+    /// Empty content is how bitmap formats signal "clear the screen".
+    pub fn is_empty(&self) -> bool {
+        match self {
+            SubtitleContent::Text(text) => text.trim().is_empty(),
+            SubtitleContent::Bitmap { rects, .. } => rects.is_empty(),
+        }
+    }
+}
+
+/// A subtitle event on the presentation timeline. `end` is `None` when the format only
+/// ends a cue with the next one (PGS, DVB).
+#[derive(Clone, Debug)]
+pub struct SubtitleCue {
+    pub start: f64,
+    pub end: Option<f64>,
+    pub content: SubtitleContent,
+}
+
+/// Why a sidecar subtitle file could not be loaded; returned to the UI as-is.
+#[derive(Clone, Debug, Serialize)]
+pub enum SubtitleFileError {
+    Io { message: String },
+    UnsupportedFormat { extension: String },
+    // `line` is 1-based
+    Parse { line: usize, message: String },
+    Empty,
+}
+
+impl std::fmt::Display for SubtitleFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubtitleFileError::Io { message } => write!(f, "could not read file: {}", message),
+            SubtitleFileError::UnsupportedFormat { extension } => {
+                write!(f, "unsupported subtitle format '{}'", extension)
+            }
+            SubtitleFileError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            SubtitleFileError::Empty => write!(f, "file contains no cues"),
+        }
+    }
+}
+
+/// A chapter mark on the presentation timeline (seconds).
+#[derive(Clone, Debug, Serialize)]
+pub struct Chapter {
+    pub index: usize,
+    pub start: f64,
+    pub end: f64,
+    pub title: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChapterStep {
+    Next,
+    Previous,
+}
+
+/// A numbered image sequence opened as one clip.
+#[derive(Clone, Debug, Serialize)]
+pub struct SequenceInfo {
+    // printf-style pattern, e.g. "/renders/shot_%04d.exr"
+    pub pattern: String,
+    pub first_frame: u64,
+    pub last_frame: u64,
+    pub frame_rate: f64,
+    // Frames present on disk
+    pub frame_count: usize,
+    // Gaps in the numbering as inclusive (first, last) ranges; the frame before holds
+    pub missing: Vec<(u64, u64)>,
+}
+
+/// A media file inside a ZIP archive, as listed for the UI.
+#[derive(Clone, Debug, Serialize)]
+pub struct ArchiveMediaEntry {
+    // Openable path, e.g. "/deliveries/day1.zip!/cam_a/clip.mov"
+    pub path: String,
+    // Path inside the archive
+    pub name: String,
+    // Uncompressed size in bytes
+    pub size: u64,
+    // Stored entries seek directly; compressed ones re-read from the start to go back
+    pub seekable: bool,
+}
@@ -1,7 +1,15 @@
+pub mod audio;
+pub mod frame;
 pub mod geometry;
+pub mod media;
 pub mod playback;
+pub mod probe;
 pub mod quality;
 
+pub use audio::*;
+pub use frame::*;
 pub use geometry::*;
+pub use media::*;
 pub use playback::*;
+pub use probe::*;
 pub use quality::*;
@@ -21,3 +21,16 @@ pub enum SyncMode {
     Realtime, // Clock sync (Frame Accurate)
     Fixed,    // Dumb sleep (Fixed Step)
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum SeekMode {
+    Keyframe, // Jump to the nearest preceding keyframe (fast scrubbing)
+    Precise,  // Decode forward from the keyframe to the exact target frame
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum AnimationLoop {
+    Forever,   // Restart animated images at the end regardless of the file
+    FileCount, // Stop after the number of plays the file asks for
+    Once,
+}
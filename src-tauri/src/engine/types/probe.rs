@@ -0,0 +1,82 @@
+use super::media::{SequenceInfo, StreamKind};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Container and stream details for a media file, as `probe_media` reports them.
+#[derive(Clone, Debug, Serialize)]
+pub struct MediaProbe {
+    pub container: String,
+    pub container_long_name: String,
+    pub duration: f64,
+    pub size: u64,
+    pub bit_rate: i64,
+    pub tags: BTreeMap<String, String>,
+    pub streams: Vec<ProbeStream>,
+    pub attached_pictures: Vec<AttachedPicture>,
+    pub animation: Option<AnimationInfo>,
+    // Set when the file is one frame of a numbered image sequence, which plays as a clip
+    pub sequence: Option<SequenceInfo>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ProbeStream {
+    pub index: usize,
+    pub kind: StreamKind,
+    pub codec: String,
+    pub codec_long_name: Option<String>,
+    pub profile: Option<String>,
+    pub level: Option<i32>,
+    pub bit_rate: Option<i64>,
+    pub duration: Option<f64>,
+    pub tags: BTreeMap<String, String>,
+    pub video: Option<VideoProbe>,
+    pub audio: Option<AudioProbe>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct VideoProbe {
+    pub width: u32,
+    pub height: u32,
+    pub pixel_format: Option<String>,
+    pub frame_rate: Option<f64>,
+    pub color_primaries: Option<String>,
+    pub color_transfer: Option<String>,
+    pub color_matrix: Option<String>,
+    pub color_range: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AudioProbe {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub channel_layout: String,
+    pub sample_format: Option<String>,
+}
+
+/// Cover art and other pictures embedded as streams.
+#[derive(Clone, Debug, Serialize)]
+pub struct AttachedPicture {
+    pub stream_index: usize,
+    pub codec: String,
+    pub width: u32,
+    pub height: u32,
+    pub size: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum AnimationFormat {
+    Gif,
+    Apng,
+    WebP,
+}
+
+/// Timing of an animated image, read from its own headers.
+#[derive(Clone, Debug, Serialize)]
+pub struct AnimationInfo {
+    pub format: AnimationFormat,
+    pub frame_count: u32,
+    // Plays the file asks for; 0 means forever
+    pub loop_count: u32,
+    // One pass through every frame, in seconds
+    pub duration: f64,
+}
@@ -15,3 +15,41 @@ pub enum AspectMode {
     Classic, // 4:3
     Wide,    // 16:9
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum DeinterlaceMode {
+    Off,   // Show frames as decoded
+    Auto,  // yadif on frames flagged interlaced, progressive frames untouched
+    Bob,   // Every field as its own line-doubled frame: doubles the frame rate
+    Yadif, // yadif on every frame, one output frame per input frame
+}
+
+/// A filter chain that passed validation but could not be built for the decoded frames;
+/// frames show unfiltered until the settings change.
+#[derive(Clone, Debug, Serialize)]
+pub struct VideoFilterFailure {
+    // The whole chain, deinterlacer included
+    pub filter: String,
+    pub error: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum ToneMapOperator {
+    Hable,    // Filmic curve: gentle shoulder, slightly darker midtones
+    Bt2390,   // ITU-R BT.2390 EETF: untouched below the knee, rolls off highlights in PQ
+    Reinhard, // Extended Reinhard with the source peak as white point
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum BackgroundMode {
+    Checkerboard,   // Grey squares, to see exactly where the video is transparent
+    Solid([u8; 3]), // An sRGB colour, e.g. a matte to judge edges against
+    Black,
+    White,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum AlphaMode {
+    Straight,      // Colour independent of alpha (what FFmpeg decoders produce)
+    Premultiplied, // Colour already multiplied by alpha, as many renders are exported
+}
@@ -41,12 +41,39 @@ pub fn run() {
             commands::open_video,
             commands::set_quality,
             commands::set_volume,
+            commands::set_downmix_mode,
+            commands::set_resample_quality,
+            commands::set_deinterlace_mode,
+            commands::set_video_filter,
+            commands::set_sequence_frame_rate,
+            commands::sequence_frame_at,
+            commands::set_animation_loop,
+            commands::set_follow_mode,
+            commands::get_audio_output_format,
             commands::toggle_playback,
             commands::update_viewport,
             commands::init_renderer,
             commands::set_aspect_ratio,
+            commands::set_tone_mapping,
+            commands::set_background,
+            commands::set_alpha_mode,
+            commands::set_alpha_only,
             commands::seek_video,
             commands::set_sync_mode,
+            commands::set_seek_mode,
+            commands::list_streams,
+            commands::select_video_stream,
+            commands::select_audio_stream,
+            commands::list_subtitle_tracks,
+            commands::select_subtitle_track,
+            commands::disable_subtitles,
+            commands::load_subtitle_file,
+            commands::set_subtitle_offset,
+            commands::list_chapters,
+            commands::next_chapter,
+            commands::previous_chapter,
+            commands::probe_media,
+            commands::list_archive_media,
             commands::get_app_cache_dir
         ])
         .on_window_event(|window, event| {
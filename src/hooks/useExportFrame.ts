@@ -1,4 +1,5 @@
 import { useCallback, useState } from 'react';
+import { invoke } from '@tauri-apps/api/core';
 import { save } from '@tauri-apps/plugin-dialog';
 import { Command } from '@tauri-apps/plugin-shell';
 import { useStore } from '../store/useStore';
@@ -43,13 +44,20 @@ export const useExportFrame = () => {
 
             setIsExporting(true);
 
+            // An image sequence exports the frame file on screen; anything else seeks in the video
+            const sequenceFrame = await invoke<string | null>('sequence_frame_at', { time: currentTime });
+            const input = sequenceFrame
+                ? ['-i', sequenceFrame]
+                // Note: -ss before -i is faster (input seeking)
+                : ['-ss', currentTime.toString(), '-i', currentItem.path];
+
             // Execute FFmpeg Sidecar
-            // Note: -ss before -i is faster (input seeking)
             const args = [
                 '-y',
-                '-ss', currentTime.toString(),
-                '-i', currentItem.path,
+                ...input,
                 '-vframes', '1',
+                // Autorotation is on by default; also square the pixels so anamorphic video matches the preview
+                '-vf', 'scale=round(iw*sar/2)*2:ih,setsar=1',
                 '-q:v', '2', // High quality for JPEG, ignored for PNG but good safety
                 filePath
             ];
@@ -4,6 +4,55 @@ import { Command } from '@tauri-apps/plugin-shell';
 import { exists, mkdir, readFile } from '@tauri-apps/plugin-fs';
 import { useStore, MediaItem } from '../store/useStore';
 
+// Mirrors the report returned by the `probe_media` command
+interface ProbeStream {
+    index: number;
+    kind: 'Video' | 'Audio' | 'Subtitle' | 'Data' | 'Attachment' | 'Unknown';
+    codec: string;
+    codec_long_name: string | null;
+    profile: string | null;
+    level: number | null;
+    bit_rate: number | null;
+    duration: number | null;
+    tags: Record<string, string>;
+    video: {
+        width: number;
+        height: number;
+        pixel_format: string | null;
+        frame_rate: number | null;
+        color_primaries: string | null;
+        color_transfer: string | null;
+        color_matrix: string | null;
+        color_range: string | null;
+    } | null;
+    audio: {
+        sample_rate: number;
+        channels: number;
+        channel_layout: string;
+        sample_format: string | null;
+    } | null;
+}
+
+interface MediaProbe {
+    container: string;
+    container_long_name: string;
+    duration: number;
+    size: number;
+    bit_rate: number;
+    tags: Record<string, string>;
+    streams: ProbeStream[];
+    attached_pictures: { stream_index: number; codec: string; width: number; height: number; size: number }[];
+    animation: { format: string; frame_count: number; loop_count: number; duration: number } | null;
+    sequence: {
+        pattern: string;
+        first_frame: number;
+        last_frame: number;
+        frame_rate: number;
+        frame_count: number;
+        missing: [number, number][];
+    } | null;
+}
+
 export function useFileProcessing() {
     const playlist = useStore((state) => state.playlist);
     const updateMediaItem = useStore((state) => state.updateMediaItem);
@@ -65,15 +114,12 @@ export function useFileProcessing() {
             }
 
             // 1. First, Probing Metadata (Fast)
-            const ffprobe = Command.sidecar('bin/ffprobe', [
-                '-v', 'error',
-                '-show_entries', 'format=duration,size,bit_rate,format_name:stream=codec_type,width,height,sample_rate,r_frame_rate,codec_name,channels,channel_layout,pix_fmt,profile,level,sample_fmt,bit_rate,color_space,color_transfer,color_primaries,color_range',
-                '-print_format', 'json',
-                item.path
-            ]);
-            // console.log(`[useFileProcessing] Starting probe for ${item.name}`); 
-            const probeResult = await ffprobe.execute();
-            // console.log(`[useFileProcessing] Probe Result for ${item.name}:`, probeResult);
+            let probe: MediaProbe | null = null;
+            try {
+                probe = await invoke<MediaProbe>('probe_media', { path: item.path });
+            } catch (e) {
+                console.warn(`[useFileProcessing] Probe failed for ${item.name}:`, e);
+            }
 
             let duration = 0;
             let size = 0;
@@ -96,77 +142,70 @@ export function useFileProcessing() {
             let colorRange = '';
             let type: 'Video' | 'Audio' | 'Image' = 'Video';
 
-            if (probeResult.code === 0) {
-                try {
-                    const data = JSON.parse(probeResult.stdout);
-                    const format = data.format || {};
-                    const videoStream = data.streams?.find((s: any) => s.codec_type === 'video');
-                    const audioStream = data.streams?.find((s: any) => s.codec_type === 'audio');
+            if (probe) {
+                // Cover art is reported as a video stream; it isn't the picture we play
+                const attached = new Set(probe.attached_pictures.map(p => p.stream_index));
+                const videoStream = probe.streams.find(s => s.kind === 'Video' && !attached.has(s.index));
+                const audioStream = probe.streams.find(s => s.kind === 'Audio');
 
-                    duration = parseFloat(format.duration || '0');
-                    size = parseInt(format.size || '0', 10);
-                    bitrate = parseInt(format.bit_rate || '0', 10);
-                    container = format.format_name;
+                duration = probe.duration;
+                size = probe.size;
+                bitrate = probe.bit_rate;
+                container = probe.container;
 
-                    if (videoStream) {
-                        width = videoStream.width;
-                        height = videoStream.height;
-                        videoCodec = videoStream.codec_name;
-                        pixelFormat = videoStream.pix_fmt;
+                if (videoStream?.video) {
+                    width = videoStream.video.width;
+                    height = videoStream.video.height;
+                    videoCodec = videoStream.codec;
+                    pixelFormat = videoStream.video.pixel_format ?? '';
 
-                        // Color Metadata
-                        colorSpace = videoStream.color_space;
-                        colorTransfer = videoStream.color_transfer;
-                        colorPrimaries = videoStream.color_primaries;
-                        colorRange = videoStream.color_range;
+                    // Color Metadata
+                    colorSpace = videoStream.video.color_matrix ?? '';
+                    colorTransfer = videoStream.video.color_transfer ?? '';
+                    colorPrimaries = videoStream.video.color_primaries ?? '';
+                    colorRange = videoStream.video.color_range ?? '';
 
-                        if (videoStream.r_frame_rate) {
-                            const [num, den] = videoStream.r_frame_rate.split('/').map(Number);
-                            if (den > 0) fps = num / den;
-                        }
-                        // Video Profile (e.g. "Main 4.1")
-                        if (videoStream.profile) {
-                            videoProfile = videoStream.profile;
-                            if (videoStream.level && videoStream.level !== 'unknown') {
-                                // converting level 51 -> 5.1 if needed, usually ffprobe gives raw number or string
-                                // For H.264, level is often just a number like 41. Let's keep it simple.
-                                videoProfile += ` ${videoStream.level}`;
-                            }
+                    fps = videoStream.video.frame_rate ?? 0;
+                    // Video Profile (e.g. "Main 41")
+                    if (videoStream.profile) {
+                        videoProfile = videoStream.profile;
+                        if (videoStream.level !== null) {
+                            videoProfile += ` ${videoStream.level}`;
                         }
-                        // Prefer stream bitrate if available, else format bitrate
-                        if (videoStream.bit_rate) bitrate = parseInt(videoStream.bit_rate, 10);
                     }
+                    // Prefer stream bitrate if available, else format bitrate
+                    if (videoStream.bit_rate) bitrate = videoStream.bit_rate;
+                }
 
-                    if (audioStream) {
-                        sampleRate = parseInt(audioStream.sample_rate || '0', 10);
-                        audioCodec = audioStream.codec_name;
-                        channels = audioStream.channels;
-                        audioLayout = audioStream.channel_layout; // e.g. "stereo"
-                        audioDepth = audioStream.sample_fmt; // e.g. "fltp"
+                if (audioStream?.audio) {
+                    sampleRate = audioStream.audio.sample_rate;
+                    audioCodec = audioStream.codec;
+                    channels = audioStream.audio.channels;
+                    audioLayout = audioStream.audio.channel_layout; // e.g. "stereo"
+                    audioDepth = audioStream.audio.sample_format ?? ''; // e.g. "fltp"
+                }
 
-                        // Map simple layouts if missing
-                        if (!audioLayout && channels > 0) {
-                            if (channels === 1) audioLayout = 'mono';
-                            if (channels === 2) audioLayout = 'stereo';
-                        }
-                    }
+                // One frame of a numbered sequence plays as a clip at the sequence frame rate
+                if (probe.sequence) {
+                    fps = probe.sequence.frame_rate;
+                }
+                // Animated GIF/APNG/WebP carry their timing in their own headers
+                if (probe.animation && duration <= 0) {
+                    duration = probe.animation.duration;
+                }
 
-                    const ext = item.path.split('.').pop()?.toLowerCase();
-                    const isImageExt = ['jpg', 'jpeg', 'png', 'webp', 'tiff', 'tif', 'bmp'].includes(ext || '');
+                const ext = item.path.split('.').pop()?.toLowerCase();
+                const isImageExt = ['jpg', 'jpeg', 'png', 'webp', 'tiff', 'tif', 'bmp'].includes(ext || '');
 
-                    if (isImageExt) {
-                        type = 'Image';
-                    } else if (!videoStream && audioStream) {
-                        type = 'Audio';
-                    } else {
-                        type = 'Video';
-                    }
-                    // console.log(`[useFileProcessing] Identified type for ${item.name}: ${type}, Duration: ${duration}`);
-                } catch (e) {
-                    console.error("Failed to parse ffprobe json:", e);
+                if (probe.sequence || probe.animation) {
+                    type = 'Video';
+                } else if (isImageExt) {
+                    type = 'Image';
+                } else if (!videoStream && audioStream) {
+                    type = 'Audio';
+                } else {
+                    type = 'Video';
                 }
-            } else {
-                console.warn(`[useFileProcessing] Probe failed for ${item.name} code: ${probeResult.code}, stderr: ${probeResult.stderr}`);
             }
 
             // 2. Generate thumbnail if not audio
@@ -176,9 +215,11 @@ export function useFileProcessing() {
                 const thumbPath = `${thumbDir}${sep}${hash}.jpg`;
 
                 if (!(await exists(thumbPath))) {
+                    // Stills, sequence frames and animations are thumbnailed from their first frame
+                    const isStill = type === 'Image' || !!probe?.sequence || !!probe?.animation;
                     const args = [
                         '-y',
-                        ...(type === 'Image' ? [] : ['-ss', '00:00:01']),
+                        ...(isStill ? [] : ['-ss', '00:00:01']),
                         '-i', item.path,
                         '-vframes', '1',
                         '-vf', 'scale=320:-1',